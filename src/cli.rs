@@ -1,3 +1,4 @@
+use crate::config::{MinGenomes, StreamedOutput};
 use clap::{builder::ValueParser, ArgAction, Parser};
 
 #[derive(Parser, Debug)]
@@ -7,13 +8,28 @@ use clap::{builder::ValueParser, ArgAction, Parser};
     about = "Parse paths from a GFF/GFA file"
 )]
 pub struct Cli {
-    /// Input graph file
-    pub graph_input: String,
+    /// Input graph file, `-` to read from stdin (requires --format).
+    /// Several files are merged, each file being one genome named after the file.
+    /// A manifest (.manifest) lists the files with their genome names.
+    #[arg(required = true, num_args = 1..)]
+    pub graph_input: Vec<String>,
+
+    /// Input format (gff, gfa, gfa2, fasta, list, maf, xmfa, paths, grimm, unimog, orthogroups), inferred from the extension by default
+    #[arg(short = 'f', long = "format", value_name = "FORMAT")]
+    pub format: Option<String>,
+
+    /// GFF3 annotation of a genome (FILE or GENOME=FILE) when the input is an orthogroup table (Orthogroups.tsv), repeated for each genome
+    #[arg(short = 'a', long = "annotation", value_name = "GFF3", action = ArgAction::Append)]
+    pub annotations: Vec<String>,
 
     /// Output directory
     #[arg(short = 'o', long = "out-dir", default_value = "mice_output")]
     pub out_dir: String,
 
+    /// Also write this output to stdout
+    #[arg(long = "stdout", value_enum, value_name = "OUTPUT")]
+    pub stdout: Option<StreamedOutput>,
+
     /// Remove an element if it occurs more than x times in any genome. Use 0 to disable removal.
     #[arg(
         short = 'r',
@@ -45,10 +61,112 @@ pub struct Cli {
     )]
     pub min_size: usize,
 
+    /// Size thresholds (in bp) of a block hierarchy, e.g. 0,1000,10000,100000.
+    /// Blocks shorter than a threshold are filtered and the others compressed into coarser blocks,
+    /// the finest level is the main output and the nesting of the levels is written to hierarchy.txt.
+    #[arg(long = "resolutions", value_name = "BP", value_delimiter = ',', num_args = 1..)]
+    pub resolutions: Vec<usize>,
+
+    /// Contract insertions of at most this many elements inside a block, when their two flanking
+    /// elements are adjacent in another genome. Use 0 for no bound on the number of elements.
+    #[arg(long = "max-gap", value_name = "N", default_value_t = 0)]
+    pub max_gap: usize,
+
+    /// Contract insertions of at most this length (in bp) inside a block, see --max-gap.
+    /// Use 0 for no bound on the length.
+    #[arg(long = "max-gap-bp", value_name = "BP", default_value_t = 0)]
+    pub max_gap_bp: usize,
+
+    /// Keep only the elements present in at least N genomes, or in a fraction of the genomes (e.g. 0.9).
+    /// The other (accessory) elements are filtered, unless --accessory is set.
+    #[arg(long = "min-genomes", value_name = "N|FRACTION")]
+    pub min_genomes: Option<MinGenomes>,
+
+    /// Compress the accessory elements of --min-genomes apart from the others instead of filtering them
+    #[arg(long = "accessory", requires = "min_genomes", action = ArgAction::SetTrue)]
+    pub accessory: bool,
+
+    /// k-mer size used to segment FASTA input into elements (at most 32)
+    #[arg(
+        short = 'k',
+        long = "kmer-size",
+        default_value_t = 31,
+        value_parser = ValueParser::new(|s: &str| -> Result<usize, String> {
+            let v: usize = s.parse().map_err(|_| "Expected a positive integer".to_string())?;
+            if v == 0 || v > 32 {
+                Err("Value must be between 1 and 32".to_string())
+            } else {
+                Ok(v)
+            }
+        })
+    )]
+    pub kmer_size: usize,
+
+    /// Minimizer window (in k-mers) used to sample the elements of FASTA input. Use 1 to keep every k-mer.
+    #[arg(
+        short = 'w',
+        long = "window",
+        default_value_t = 10,
+        value_parser = ValueParser::new(|s: &str| -> Result<usize, String> {
+            let v: usize = s.parse().map_err(|_| "Expected a positive integer".to_string())?;
+            if v == 0 {
+                Err("Value 0 is not allowed".to_string())
+            } else {
+                Ok(v)
+            }
+        })
+    )]
+    pub window: usize,
+
     /// If set every path is treated as its own genome
     #[arg(short = 's', long = "no-group-by", default_value_t = true, action = ArgAction::SetFalse)]
     pub group_by: bool,
 
+    /// GFF attribute naming the element of a feature, e.g. Name, orthogroup or cluster
+    #[arg(long = "id-attribute", value_name = "NAME", default_value = "ID")]
+    pub id_attribute: String,
+
+    /// Genome of a GFF feature from this attribute (default: genome)
+    #[arg(long = "genome-attribute", value_name = "NAME", group = "genome_rule")]
+    pub genome_attribute: Option<String>,
+
+    /// Genome of a path from its name before the first delimiter, e.g. '.' for Genome1.Chr2 or '#' for PanSN (GFA default)
+    #[arg(long = "genome-delimiter", value_name = "CHAR", group = "genome_rule")]
+    pub genome_delimiter: Option<char>,
+
+    /// Genome of a path from the first capture group (or the whole match) of a regex on its name
+    #[arg(long = "genome-regex", value_name = "REGEX", group = "genome_rule")]
+    pub genome_regex: Option<String>,
+
+    /// Genome of each path from a file of `path<TAB>genome` lines
+    #[arg(long = "genome-map", value_name = "FILE", group = "genome_rule")]
+    pub genome_map: Option<String>,
+
+    /// File of circular path names, one per line, in addition to the GFA paths tagged TP:Z:circular and the GFF regions with Is_circular=true
+    #[arg(long = "circular", value_name = "FILE")]
+    pub circular: Option<String>,
+
+    /// Renumber the blocks by first occurrence in a reference genome, then by size, and write block_ids.txt
+    #[arg(long = "renumber", action = ArgAction::SetTrue)]
+    pub renumber: bool,
+
+    /// Reference genome for --renumber (default: the first genome). Implies --renumber
+    #[arg(long = "reference", value_name = "GENOME")]
+    pub reference: Option<String>,
+
+    /// Order genomes and paths by name in the outputs instead of their first appearance in the input
+    #[arg(long = "sort", action = ArgAction::SetTrue)]
+    pub sort: bool,
+
+    /// Abort on the first malformed record of the input instead of skipping it
+    #[arg(long = "strict", action = ArgAction::SetTrue)]
+    pub strict: bool,
+
+    /// Split an element repeated in a genome into one element per flanking context, so that each copy
+    /// joins its own block, and write the blocks holding copies of the same element to paralogs.txt
+    #[arg(long = "split-paralogs", action = ArgAction::SetTrue)]
+    pub split_paralogs: bool,
+
     /// Avoid marking duplicated elements in each genome and merge them like they were unique
     #[arg(long = "dirty", hide = true, action = ArgAction::SetTrue)]
     pub dirty: bool,
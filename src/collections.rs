@@ -1,2 +1,6 @@
 //pub use std::collections::{HashMap, HashSet};
 pub use rustc_hash::{FxHashMap as HashMap, FxHashSet as HashSet};
+
+// Maps iterated when writing outputs keep the insertion order, so outputs follow the input
+pub type IndexMap<K, V> = indexmap::IndexMap<K, V, rustc_hash::FxBuildHasher>;
+pub use indexmap::map::Entry as IndexMapEntry;
@@ -1,59 +1,246 @@
 use crate::io::*;
 use std::fs;
-use anyhow::Result;
-use crate::collections::{HashSet, HashMap};
-use std::path;
-use crate::cli::Cli;
+use anyhow::{bail, Result};
+use crate::collections::{HashMap, HashSet, IndexMap};
+use crate::config::MiceConfig;
+use crate::synteny::{BlockClass, IntervalKind, SyntenyLevel, SyntenyResult};
 
 pub fn run_mice(
-    args: &Cli,
+    config: &MiceConfig,
 ) -> Result<()> {
-    // Args
-    let out_dir = path::Path::new(&args.out_dir);
+    let out_dir = config.out_dir.as_path();
     if !out_dir.exists() {
         fs::create_dir_all(out_dir)?;
     }
 
-    let graph_input = &args.graph_input;
-    let remove_duplicates = args.remove_duplicates;
-    let group_by = args.group_by;
-    let min_size = args.min_size;
-    let dirty = args.dirty;
-    let force_ext = None;
+    // The finest level of a block hierarchy is the result
+    let (result, levels) = if config.resolutions.is_empty() {
+        (compute_synteny(config)?, Vec::new())
+    } else {
+        let levels = compute_hierarchy(config)?;
+        (levels[0].result.clone(), levels)
+    };
+
+    eprintln!("num genomes:\t{}", result.genomes.len());
+    eprintln!("num paths:\t{}", result.num_paths);
+    eprintln!("num nodes:\t{}", result.num_nodes);
+    eprintln!("num partitions:\t{}", result.blocks.len());
+    eprintln!("ratio:\t\t{:.2}", result.blocks.len() as f64 / result.num_nodes as f64);
+    for class in [BlockClass::Core, BlockClass::Shell, BlockClass::Cloud] {
+        let num_blocks = result.blocks.iter().filter(|block| block.class == class).count();
+        eprintln!("num {class} blocks:\t{num_blocks}");
+    }
 
-    // Lets go
-    let (graph_bundle, path_bundle, partition_bundle) = load_graph(graph_input, force_ext, remove_duplicates, group_by, dirty)?;
+    if !levels.is_empty() {
+        write_hierarchy(out_dir, &levels)?;
+    }
+    if config.outputs.paths {
+        write_paths(out_dir, &result)?;
+    }
+    if config.outputs.partitions {
+        write_partition(out_dir, &result)?;
+    }
+    if config.outputs.blocks {
+        write_output(config, &result)?;
+    }
+    if config.renumber {
+        write_block_ids(out_dir, &result)?;
+    }
+    if config.split_paralogs {
+        write_paralogs(out_dir, &result)?;
+    }
+    if let Some(output) = config.stdout {
+        write_stdout(output, &result)?;
+    }
 
-    let GraphBundle { mut graph, num_nodes, duplicates, } = graph_bundle;
-    let GenomeBundle { genomes, num_paths, node_indexer, } = path_bundle;
+    Ok(())
+}
 
-    let mut partition_bundle = compress_graph(&mut graph, num_nodes, partition_bundle, &duplicates);
+/// Computes the synteny blocks without writing anything to the output directory
+pub fn compute_synteny(
+    config: &MiceConfig,
+) -> Result<SyntenyResult> {
+    let min_size = config.min_size;
+
+    // Lets go
+    let (mut input, graph, partition_bundle) = CompressionInput::load(config)?;
+    let mut partition_bundle = input.compress(graph, partition_bundle);
 
     if min_size != 0 {
-        filter_min_size(&mut partition_bundle.node_to_part, num_nodes, &genomes, min_size);
-        graph = update_graph(&genomes, num_nodes, &partition_bundle.node_to_part);
-        partition_bundle = compress_graph(&mut graph, num_nodes, partition_bundle, &duplicates);
+        filter_min_size(&mut partition_bundle.node_to_part, input.num_nodes, &input.genomes, min_size)?;
+        partition_bundle = input.recompress(partition_bundle);
+    }
+
+    let node_indexer = input.node_indexer.take();
+    input.result(config, &partition_bundle.node_to_part, node_indexer)
+}
+
+/// Computes the synteny blocks at every threshold of `config.resolutions`, from the finest to the
+/// coarsest. At each threshold the blocks whose occurrences are all shorter than the threshold are
+/// filtered, and the remaining blocks are compressed again into coarser blocks.
+pub fn compute_hierarchy(
+    config: &MiceConfig,
+) -> Result<Vec<SyntenyLevel>> {
+    let (input, graph, partition_bundle) = CompressionInput::load(config)?;
+    if input.genomes.values().any(|g| g.path_starts.len() != g.paths.len()) {
+        bail!("--resolutions requires element coordinates, which are missing from the input");
     }
+    let mut partition_bundle = input.compress(graph, partition_bundle);
+
+    let mut levels: Vec<SyntenyLevel> = Vec::with_capacity(config.resolutions.len());
+    let mut node_to_parts: Vec<Vec<usize>> = Vec::with_capacity(config.resolutions.len());
+    for &threshold in config.resolutions.iter() {
+        if threshold > 0 {
+            // Blocks of the previous level, or of the first compression
+            let unfiltered;
+            let finer = match levels.last() {
+                Some(level) => &level.result,
+                None => {
+                    unfiltered = input.blocks(&partition_bundle.node_to_part, None);
+                    &unfiltered
+                }
+            };
+            filter_short_blocks(&mut partition_bundle.node_to_part, finer, threshold);
+            partition_bundle = input.recompress(partition_bundle);
+        }
 
-    eprintln!("num genomes:\t{}", genomes.len());
-    eprintln!("num paths:\t{}", num_paths);
-    eprintln!("num nodes:\t{}", num_nodes);
-    eprintln!("num partitions:\t{}", partition_bundle.num_parts);
-    eprintln!("ratio:\t\t{:.2}", partition_bundle.num_parts as f64 / num_nodes as f64);
+        let result = input.result(config, &partition_bundle.node_to_part, input.node_indexer.clone())?;
+        eprintln!("num partitions at {threshold} bp:\t{}", result.blocks.len());
+        levels.push(SyntenyLevel {
+            threshold,
+            result,
+            parents: Vec::new(),
+        });
+        node_to_parts.push(partition_bundle.node_to_part.clone());
+    }
 
-    write_paths(out_dir, &genomes, &partition_bundle.node_to_part)?;
-    write_partition(out_dir, num_nodes, &partition_bundle.node_to_part, node_indexer)?;
+    // The core element of a block is in its parent block at the next level
+    for i in 0..levels.len().saturating_sub(1) {
+        let next_ids: HashMap<usize, usize> = levels[i + 1]
+            .result
+            .blocks
+            .iter()
+            .map(|block| (block.part_id, block.id))
+            .collect();
+        let next_node_to_part = &node_to_parts[i + 1];
+        levels[i].parents = levels[i]
+            .result
+            .blocks
+            .iter()
+            .map(|block| match next_node_to_part[block.part_id - 1] {
+                FILTERED => None,
+                part => next_ids.get(&(part + 1)).copied(),
+            })
+            .collect();
+    }
+    if let Some(coarsest) = levels.last_mut() {
+        coarsest.parents = vec![None; coarsest.result.blocks.len()];
+    }
 
-    write_output(graph_input, force_ext, out_dir, &genomes, &partition_bundle.node_to_part)?;
+    Ok(levels)
+}
 
-    Ok(())
+// The genomes and element sets of a loaded input, shared by the successive compressions of a run
+struct CompressionInput {
+    genomes: IndexMap<String, PathBundle>,
+    num_nodes: usize,
+    num_paths: usize,
+    node_indexer: Option<NodeIndexer>,
+    duplicates: HashSet<usize>,
+    accessory: HashSet<usize>,
+    paralogs: Vec<(String, Vec<usize>)>,
+    genome_counts: Vec<usize>,
+}
+
+impl CompressionInput {
+    // Also returns the graph of the elements and their initial partition, compressed by compress
+    fn load(config: &MiceConfig) -> Result<(Self, Vec<Vec<usize>>, PartitionBundle)> {
+        let (graph_bundle, path_bundle, partition_bundle) = load_graph(config)?;
+
+        let GraphBundle { graph, num_nodes, duplicates, accessory, paralogs, genome_counts, } = graph_bundle;
+        let GenomeBundle { genomes, num_paths, node_indexer, } = path_bundle;
+        let input = Self {
+            genomes,
+            num_nodes,
+            num_paths,
+            node_indexer,
+            duplicates,
+            accessory,
+            paralogs,
+            genome_counts,
+        };
+        Ok((input, graph, partition_bundle))
+    }
+
+    fn compress(&self, graph: Vec<Vec<usize>>, partition_bundle: PartitionBundle) -> PartitionBundle {
+        compress_partitions(graph, &self.genomes, self.num_nodes, partition_bundle, &self.duplicates, &self.accessory)
+    }
+
+    // Compresses again after elements were filtered from the partition
+    fn recompress(&self, partition_bundle: PartitionBundle) -> PartitionBundle {
+        let graph = update_graph(&self.genomes, self.num_nodes, &partition_bundle.node_to_part);
+        self.compress(graph, partition_bundle)
+    }
+
+    fn blocks(&self, node_to_part: &[usize], node_indexer: Option<NodeIndexer>) -> SyntenyResult {
+        SyntenyResult::new(
+            &self.genomes,
+            self.num_nodes,
+            self.num_paths,
+            node_to_part,
+            node_indexer,
+            &self.genome_counts,
+            &self.accessory,
+        )
+    }
+
+    // The blocks as reported, with their paralogs and renumbered if configured
+    fn result(
+        &self,
+        config: &MiceConfig,
+        node_to_part: &[usize],
+        node_indexer: Option<NodeIndexer>,
+    ) -> Result<SyntenyResult> {
+        let mut result = self.blocks(node_to_part, node_indexer);
+        result.set_paralogs(&self.paralogs, node_to_part);
+        if config.renumber {
+            result.renumber(config.reference.as_deref())?;
+        }
+        Ok(result)
+    }
+}
+
+// Filters the elements of the blocks whose occurrences are all shorter than min_size
+fn filter_short_blocks(node_to_part: &mut [usize], result: &SyntenyResult, min_size: usize) {
+    // Interval ids may be renumbered
+    let part_ids: HashMap<usize, usize> = result.blocks.iter().map(|block| (block.id, block.part_id)).collect();
+    let mut longest: HashMap<usize, usize> = HashMap::default();
+    for genome in result.genomes.iter() {
+        for path in genome.paths.iter() {
+            for interval in path.intervals.iter().flatten() {
+                if let IntervalKind::Block(id) = interval.kind {
+                    let length = longest.entry(part_ids[&id]).or_insert(0);
+                    *length = (*length).max(interval.end + 1 - interval.start);
+                }
+            }
+        }
+    }
+    for part in node_to_part.iter_mut() {
+        if *part != FILTERED && longest.get(&(*part + 1)).is_none_or(|&length| length < min_size) {
+            *part = FILTERED;
+        }
+    }
 }
 
 fn filter_min_size(
     node_to_part: &mut [usize], 
     num_nodes: usize, 
-    genomes: &HashMap<String, PathBundle>,
-    min_size: usize, ) {
+    genomes: &IndexMap<String, PathBundle>,
+    min_size: usize, ) -> Result<()> {
+    if genomes.values().any(|g| g.path_starts.len() != g.paths.len()) {
+        bail!("--min-size requires element coordinates, which are missing from the input");
+    }
+
     let mut part_counts = vec![0usize; num_nodes];
     for i in 0..num_nodes {
         if node_to_part[i] != FILTERED {
@@ -65,13 +252,52 @@ fn filter_min_size(
             let path_starts = &genome.path_starts[i];
             let path_ends = &genome.path_ends[i];
             for (j, el) in path.iter().enumerate() {
-                //TODO this works for GFF but GFA has no path_ends or path_starts
-                if part_counts[el.id] == 1 && (path_ends[j] - path_starts[j] + 1) < min_size {
+                if part_counts[el.id] == 1 && (path_ends[j] + 1 - path_starts[j]) < min_size {
                     node_to_part[el.id] = FILTERED;
                 }
             }
         }
     }
+
+    Ok(())
+}
+
+// Compresses the graph. With accessory elements, the other elements are first compressed with the
+// accessory elements filtered, so that their blocks span the accessory regions. The accessory
+// elements are then compressed on their own, the other elements being kept apart like duplicates,
+// so that an accessory block stays within one accessory region.
+fn compress_partitions(
+    mut graph: Vec<Vec<usize>>,
+    genomes: &IndexMap<String, PathBundle>,
+    num_nodes: usize,
+    partition_bundle: PartitionBundle,
+    duplicates: &HashSet<usize>,
+    accessory: &HashSet<usize>,
+) -> PartitionBundle {
+    if accessory.is_empty() {
+        return compress_graph(&mut graph, num_nodes, partition_bundle, duplicates);
+    }
+
+    let mut core_parts = partition_bundle.node_to_part.clone();
+    for &el in accessory.iter() {
+        core_parts[el] = FILTERED;
+    }
+    let mut graph = update_graph(genomes, num_nodes, &core_parts);
+    let core = compress_graph(&mut graph, num_nodes, PartitionBundle { node_to_part: core_parts, num_parts: 0 }, duplicates);
+
+    let mut kept_apart = duplicates.clone();
+    kept_apart.extend((0..num_nodes).filter(|el| !accessory.contains(el)));
+    let mut graph = update_graph(genomes, num_nodes, &partition_bundle.node_to_part);
+    let nested = compress_graph(&mut graph, num_nodes, partition_bundle, &kept_apart);
+
+    let node_to_part = core
+        .node_to_part
+        .iter()
+        .zip(nested.node_to_part.iter())
+        .enumerate()
+        .map(|(el, (&core_part, &nested_part))| if accessory.contains(&el) { nested_part } else { core_part })
+        .collect();
+    connected_components(node_to_part, num_nodes)
 }
 
 fn connected_components(mut node_to_part: Vec<usize>, num_nodes: usize) -> PartitionBundle {
@@ -204,13 +430,14 @@ fn is_adj_size_one(adj: &mut [usize], next: &mut usize, node_to_part: &[usize])
     false // Degree != 1
 }
 
-#[allow(dead_code)]
 pub fn run_mice_test(input: &str, force_ext: Option<&str>) -> Result<usize> {
-    let remove_duplicates = 0usize;
-    let group_by = false;
-    let dirty = false;
-    let (graph_bundle, genome_bundle, partition_bundle) = load_graph(input, force_ext, remove_duplicates, group_by, dirty)?;
-    let GraphBundle { mut graph, num_nodes, duplicates, } = graph_bundle;
+    let mut builder = MiceConfig::builder(input).group_by(false);
+    if let Some(ext) = force_ext {
+        builder = builder.format(ext);
+    }
+    let config = builder.build()?;
+    let (graph_bundle, genome_bundle, partition_bundle) = load_graph(&config)?;
+    let GraphBundle { mut graph, num_nodes, duplicates, .. } = graph_bundle;
 
     let partition_bundle = compress_graph(&mut graph, num_nodes, partition_bundle, &duplicates);
 
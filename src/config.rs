@@ -0,0 +1,476 @@
+use crate::cli::Cli;
+use crate::collections::HashMap;
+use crate::io::KmerParams;
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::path::PathBuf;
+
+/// Which files are written in the output directory
+#[derive(Clone, Debug)]
+pub struct OutputTargets {
+    /// `paths.txt`: genomes rewritten as synteny blocks
+    pub paths: bool,
+    /// `partitions.txt`: elements contained in each synteny block
+    pub partitions: bool,
+    /// `output.gff` (and `output.gfa` for GFA input): block annotations
+    pub blocks: bool,
+}
+
+impl Default for OutputTargets {
+    fn default() -> Self {
+        Self {
+            paths: true,
+            partitions: true,
+            blocks: true,
+        }
+    }
+}
+
+/// One input file of a multi-file run, with the genome it holds (named after the file if None)
+#[derive(Clone, Debug)]
+pub struct GenomeFile {
+    pub file: String,
+    pub genome: Option<String>,
+}
+
+/// How the genome of a path is found when grouping the paths by genome
+#[derive(Clone, Debug)]
+pub enum GenomeRule {
+    /// Value of a GFF attribute (GFF default: `genome`)
+    Attribute(String),
+    /// Path name before the first delimiter, e.g. `.` for `Genome1.Chr2` (GFA default: `#`, PanSN)
+    Delimiter(char),
+    /// First capture group of a regex on the path name, or the whole match without group
+    Regex(Regex),
+    /// Path name to genome
+    Mapping(HashMap<String, String>),
+}
+
+impl GenomeRule {
+    pub fn regex(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).with_context(|| format!("invalid genome regex '{pattern}'"))?;
+        Ok(GenomeRule::Regex(regex))
+    }
+
+    /// Reads a mapping file, one `path<TAB>genome` per line
+    pub fn mapping_file(filename: &str) -> Result<Self> {
+        let content =
+            std::fs::read_to_string(filename).with_context(|| format!("Error reading genome map {filename}"))?;
+        let mut mapping = HashMap::default();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('\t') {
+                Some((path, genome)) => mapping.insert(path.trim().to_string(), genome.trim().to_string()),
+                None => bail!("{filename}:{}: expected path<TAB>genome", i + 1),
+            };
+        }
+        Ok(GenomeRule::Mapping(mapping))
+    }
+
+    /// Genome of a path from its name, None if the rule does not match (or reads an attribute)
+    pub fn genome_from_name(&self, path_name: &str) -> Option<String> {
+        match self {
+            GenomeRule::Attribute(_) => None,
+            GenomeRule::Delimiter(delimiter) => {
+                Some(path_name.split_once(*delimiter).map_or(path_name, |(genome, _)| genome).to_string())
+            }
+            GenomeRule::Regex(regex) => {
+                let captures = regex.captures(path_name)?;
+                captures.get(1).or_else(|| captures.get(0)).map(|m| m.as_str().to_string())
+            }
+            GenomeRule::Mapping(mapping) => mapping.get(path_name).cloned(),
+        }
+    }
+}
+
+/// Minimum number of genomes containing an element for the element to form blocks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MinGenomes {
+    Count(usize),
+    /// Fraction of the genomes, rounded up
+    Fraction(f64),
+}
+
+impl MinGenomes {
+    pub fn count(&self, num_genomes: usize) -> usize {
+        match *self {
+            MinGenomes::Count(count) => count,
+            MinGenomes::Fraction(fraction) => (fraction * num_genomes as f64).ceil() as usize,
+        }
+    }
+}
+
+impl std::str::FromStr for MinGenomes {
+    type Err = String;
+
+    // An integer is a number of genomes, a decimal number a fraction of the genomes
+    fn from_str(s: &str) -> Result<Self, String> {
+        if s.contains('.') {
+            let fraction: f64 = s.parse().map_err(|_| format!("invalid fraction '{s}'"))?;
+            if !(fraction > 0.0 && fraction <= 1.0) {
+                return Err("a fraction of the genomes must be in (0, 1]".to_string());
+            }
+            Ok(MinGenomes::Fraction(fraction))
+        } else {
+            let count: usize = s.parse().map_err(|_| "Expected a positive integer or a fraction".to_string())?;
+            if count == 0 {
+                return Err("Value 0 is not allowed".to_string());
+            }
+            Ok(MinGenomes::Count(count))
+        }
+    }
+}
+
+/// An output that can be streamed to stdout
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum StreamedOutput {
+    /// `paths.txt`
+    Paths,
+    /// `partitions.txt`
+    Partitions,
+    /// `block_ids.txt`
+    BlockIds,
+    /// `output.gff`, needs element coordinates
+    Gff,
+    /// `output.gfa`
+    Gfa,
+}
+
+/// Settings of a mice run, independent of the command line
+#[derive(Clone, Debug)]
+pub struct MiceConfig {
+    /// Input graph file, `-` for stdin
+    pub input: String,
+    /// Input format (e.g. "gff", "gfa", "fasta"), inferred from the extension if None, required for stdin
+    pub format: Option<String>,
+    /// Input files merged into one run, `input` is ignored if not empty
+    pub genome_files: Vec<GenomeFile>,
+    /// GFF3 annotations of the genomes (FILE or GENOME=FILE) for an orthogroup table input
+    pub annotations: Vec<String>,
+    /// Remove an element if it occurs more than x times in any genome, 0 disables removal
+    pub remove_duplicates: usize,
+    /// Minimum element length (in bp) to keep elements that were not merged after the first compression
+    pub min_size: usize,
+    /// Size thresholds (in bp) of a block hierarchy, increasing, empty for a single level
+    pub resolutions: Vec<usize>,
+    /// Maximum number of elements of an insertion contracted inside a block, 0 = no bound
+    pub max_gap: usize,
+    /// Maximum length (in bp) of an insertion contracted inside a block, 0 = no bound.
+    /// Insertions are contracted only if max_gap or max_gap_bp is set.
+    pub max_gap_bp: usize,
+    /// Elements present in fewer genomes (accessory elements) are filtered, None keeps every element
+    pub min_genomes: Option<MinGenomes>,
+    /// Compress the accessory elements apart from the core elements instead of filtering them
+    pub compress_accessory: bool,
+    /// Split the elements duplicated in a genome into one element per flanking context
+    pub split_paralogs: bool,
+    /// Group paths by genome, otherwise every path is its own genome
+    pub group_by: bool,
+    /// How the genome of a path is found, the default of the input format if None
+    pub genome_rule: Option<GenomeRule>,
+    /// GFF attribute naming the element of a feature
+    pub id_attribute: String,
+    /// Names of circular paths, in addition to the paths marked circular in the input
+    pub circular_paths: Vec<String>,
+    /// Merge duplicated elements like they were unique
+    pub dirty: bool,
+    /// Abort on the first malformed record instead of skipping it
+    pub strict: bool,
+    /// Order genomes and paths by name instead of first appearance in the input
+    pub sort: bool,
+    /// Renumber the blocks by first occurrence in the reference genome, then by size
+    pub renumber: bool,
+    /// Reference genome used to renumber the blocks, the first genome if None
+    pub reference: Option<String>,
+    /// k-mer size and minimizer window for FASTA input
+    pub kmer_params: KmerParams,
+    /// Output directory
+    pub out_dir: PathBuf,
+    /// Files written in the output directory
+    pub outputs: OutputTargets,
+    /// Output also written to stdout
+    pub stdout: Option<StreamedOutput>,
+}
+
+impl MiceConfig {
+    pub fn builder(input: impl Into<String>) -> MiceConfigBuilder {
+        let mut builder = Self::genome_files_builder();
+        builder.config.input = input.into();
+        builder
+    }
+
+    /// Builder of a run over several input files, each added with `genome_file`
+    pub fn genome_files_builder() -> MiceConfigBuilder {
+        MiceConfigBuilder {
+            config: MiceConfig {
+                input: String::new(),
+                format: None,
+                genome_files: Vec::new(),
+                annotations: Vec::new(),
+                remove_duplicates: 0,
+                min_size: 0,
+                resolutions: Vec::new(),
+                max_gap: 0,
+                max_gap_bp: 0,
+                min_genomes: None,
+                compress_accessory: false,
+                split_paralogs: false,
+                group_by: true,
+                genome_rule: None,
+                id_attribute: "ID".to_string(),
+                circular_paths: Vec::new(),
+                dirty: false,
+                strict: false,
+                sort: false,
+                renumber: false,
+                reference: None,
+                kmer_params: KmerParams { k: 31, w: 10 },
+                out_dir: PathBuf::from("mice_output"),
+                outputs: OutputTargets::default(),
+                stdout: None,
+            },
+        }
+    }
+}
+
+pub struct MiceConfigBuilder {
+    config: MiceConfig,
+}
+
+impl MiceConfigBuilder {
+    pub fn format(mut self, format: impl Into<String>) -> Self {
+        self.config.format = Some(format.into());
+        self
+    }
+
+    /// Adds an input file holding a genome, named after the file if `genome` is None
+    pub fn genome_file(mut self, file: impl Into<String>, genome: Option<String>) -> Self {
+        self.config.genome_files.push(GenomeFile {
+            file: file.into(),
+            genome,
+        });
+        self
+    }
+
+    pub fn annotations<I, S>(mut self, annotations: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.annotations = annotations.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn remove_duplicates(mut self, remove_duplicates: usize) -> Self {
+        self.config.remove_duplicates = remove_duplicates;
+        self
+    }
+
+    pub fn min_size(mut self, min_size: usize) -> Self {
+        self.config.min_size = min_size;
+        self
+    }
+
+    /// Computes a block hierarchy, one level per size threshold
+    pub fn resolutions(mut self, resolutions: impl Into<Vec<usize>>) -> Self {
+        self.config.resolutions = resolutions.into();
+        self
+    }
+
+    pub fn max_gap(mut self, max_gap: usize) -> Self {
+        self.config.max_gap = max_gap;
+        self
+    }
+
+    pub fn max_gap_bp(mut self, max_gap_bp: usize) -> Self {
+        self.config.max_gap_bp = max_gap_bp;
+        self
+    }
+
+    pub fn min_genomes(mut self, min_genomes: MinGenomes) -> Self {
+        self.config.min_genomes = Some(min_genomes);
+        self
+    }
+
+    pub fn compress_accessory(mut self, compress_accessory: bool) -> Self {
+        self.config.compress_accessory = compress_accessory;
+        self
+    }
+
+    pub fn split_paralogs(mut self, split_paralogs: bool) -> Self {
+        self.config.split_paralogs = split_paralogs;
+        self
+    }
+
+    pub fn group_by(mut self, group_by: bool) -> Self {
+        self.config.group_by = group_by;
+        self
+    }
+
+    pub fn genome_rule(mut self, genome_rule: GenomeRule) -> Self {
+        self.config.genome_rule = Some(genome_rule);
+        self
+    }
+
+    pub fn id_attribute(mut self, id_attribute: impl Into<String>) -> Self {
+        self.config.id_attribute = id_attribute.into();
+        self
+    }
+
+    pub fn circular_paths<I, S>(mut self, circular_paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config.circular_paths = circular_paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn dirty(mut self, dirty: bool) -> Self {
+        self.config.dirty = dirty;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.config.strict = strict;
+        self
+    }
+
+    pub fn sort(mut self, sort: bool) -> Self {
+        self.config.sort = sort;
+        self
+    }
+
+    pub fn renumber(mut self, renumber: bool) -> Self {
+        self.config.renumber = renumber;
+        self
+    }
+
+    /// Sets the reference genome, and enables the renumbering of the blocks
+    pub fn reference(mut self, reference: impl Into<String>) -> Self {
+        self.config.renumber = true;
+        self.config.reference = Some(reference.into());
+        self
+    }
+
+    pub fn kmer_size(mut self, k: usize) -> Self {
+        self.config.kmer_params.k = k;
+        self
+    }
+
+    pub fn window(mut self, w: usize) -> Self {
+        self.config.kmer_params.w = w;
+        self
+    }
+
+    pub fn out_dir(mut self, out_dir: impl Into<PathBuf>) -> Self {
+        self.config.out_dir = out_dir.into();
+        self
+    }
+
+    pub fn outputs(mut self, outputs: OutputTargets) -> Self {
+        self.config.outputs = outputs;
+        self
+    }
+
+    pub fn stdout(mut self, output: StreamedOutput) -> Self {
+        self.config.stdout = Some(output);
+        self
+    }
+
+    pub fn build(self) -> Result<MiceConfig> {
+        let config = self.config;
+        if config.input.is_empty() && config.genome_files.is_empty() {
+            bail!("no input file given");
+        }
+        if config.genome_files.iter().any(|g| g.file == "-") {
+            bail!("stdin (-) cannot be one of several inputs");
+        }
+        if config.input == "-" && config.genome_files.is_empty() && config.format.is_none() {
+            bail!("reading the input from stdin (-) needs an explicit format (--format)");
+        }
+        if config.resolutions.windows(2).any(|w| w[0] >= w[1]) {
+            bail!("the resolutions must be increasing");
+        }
+        if !config.resolutions.is_empty() && config.min_size != 0 {
+            bail!("--min-size cannot be combined with --resolutions, whose first threshold filters the smallest blocks");
+        }
+        if config.compress_accessory && config.min_genomes.is_none() {
+            bail!("compressing the accessory elements needs a minimum number of genomes (--min-genomes)");
+        }
+        if config.remove_duplicates == 1 {
+            bail!("remove_duplicates cannot be 1");
+        }
+        if config.kmer_params.k == 0 || config.kmer_params.k > 32 {
+            bail!("k must be between 1 and 32");
+        }
+        if config.kmer_params.w == 0 {
+            bail!("the window size must be at least 1");
+        }
+        Ok(config)
+    }
+}
+
+impl TryFrom<&Cli> for MiceConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(args: &Cli) -> Result<Self> {
+        // Several inputs: one genome per file
+        let builder = if args.graph_input.len() > 1 {
+            args.graph_input
+                .iter()
+                .fold(MiceConfig::genome_files_builder(), |builder, file| builder.genome_file(file, None))
+        } else {
+            MiceConfig::builder(args.graph_input[0].clone())
+        };
+        let mut builder = builder
+            .annotations(&args.annotations)
+            .remove_duplicates(args.remove_duplicates)
+            .min_size(args.min_size)
+            .resolutions(args.resolutions.clone())
+            .max_gap(args.max_gap)
+            .max_gap_bp(args.max_gap_bp)
+            .compress_accessory(args.accessory)
+            .split_paralogs(args.split_paralogs)
+            .group_by(args.group_by)
+            .id_attribute(&args.id_attribute)
+            .dirty(args.dirty)
+            .strict(args.strict)
+            .sort(args.sort)
+            .renumber(args.renumber)
+            .kmer_size(args.kmer_size)
+            .window(args.window)
+            .out_dir(&args.out_dir);
+        if let Some(format) = &args.format {
+            builder = builder.format(format);
+        }
+        if let Some(min_genomes) = args.min_genomes {
+            builder = builder.min_genomes(min_genomes);
+        }
+        if let Some(attribute) = &args.genome_attribute {
+            builder = builder.genome_rule(GenomeRule::Attribute(attribute.clone()));
+        } else if let Some(delimiter) = args.genome_delimiter {
+            builder = builder.genome_rule(GenomeRule::Delimiter(delimiter));
+        } else if let Some(pattern) = &args.genome_regex {
+            builder = builder.genome_rule(GenomeRule::regex(pattern)?);
+        } else if let Some(file) = &args.genome_map {
+            builder = builder.genome_rule(GenomeRule::mapping_file(file)?);
+        }
+        if let Some(file) = &args.circular {
+            let content =
+                std::fs::read_to_string(file).with_context(|| format!("Error reading circular paths {file}"))?;
+            let names = content.lines().map(str::trim).filter(|name| !name.is_empty() && !name.starts_with('#'));
+            builder = builder.circular_paths(names);
+        }
+        if let Some(reference) = &args.reference {
+            builder = builder.reference(reference);
+        }
+        if let Some(output) = args.stdout {
+            builder = builder.stdout(output);
+        }
+        builder.build()
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt;
+use std::io;
+
+/// Errors raised while reading the input
+#[derive(Debug)]
+pub enum MiceError {
+    /// The file cannot be opened
+    Open { file: String, source: io::Error },
+    /// The file cannot be read
+    Read { file: String, source: io::Error },
+    /// A record of the file is malformed (line is 1-based)
+    Malformed { file: String, line: usize, reason: String },
+    /// A path of a GFF is not declared in a ##sequence-region header
+    MissingSequenceRegion { file: String, path: String },
+    /// The input format cannot be found or is not supported
+    UnsupportedFormat { file: String, reason: String },
+    /// The file contains no path to read the genomes from
+    NoPaths { file: String, reason: String },
+}
+
+impl fmt::Display for MiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MiceError::Open { file, .. } => write!(f, "cannot open {file}"),
+            MiceError::Read { file, .. } => write!(f, "cannot read {file}"),
+            MiceError::Malformed { file, line, reason } => write!(f, "{file}:{line}: {reason}"),
+            MiceError::MissingSequenceRegion { file, path } => {
+                write!(f, "{file}: no ##sequence-region header for {path}")
+            }
+            MiceError::UnsupportedFormat { file, reason } => write!(f, "{file}: {reason}"),
+            MiceError::NoPaths { file, reason } => write!(f, "{file}: no usable paths ({reason})"),
+        }
+    }
+}
+
+impl std::error::Error for MiceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MiceError::Open { source, .. } | MiceError::Read { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Malformed records of a file: in strict mode the first one is an error,
+/// otherwise they are skipped and summarized at the end of the parsing
+pub struct ParseReport {
+    file: String,
+    strict: bool,
+    skipped: Vec<MiceError>,
+}
+
+impl ParseReport {
+    // Number of skipped records printed in the summary
+    const MAX_SHOWN: usize = 10;
+
+    pub fn new(file: &str, strict: bool) -> Self {
+        Self {
+            file: file.to_string(),
+            strict,
+            skipped: Vec::new(),
+        }
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn error(&self, line: usize, reason: impl Into<String>) -> MiceError {
+        MiceError::Malformed {
+            file: self.file.clone(),
+            line,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn read_error(&self, source: io::Error) -> MiceError {
+        MiceError::Read {
+            file: self.file.clone(),
+            source,
+        }
+    }
+
+    /// Records a malformed record, fails in strict mode
+    pub fn skip(&mut self, err: MiceError) -> Result<(), MiceError> {
+        if self.strict {
+            return Err(err);
+        }
+        self.skipped.push(err);
+        Ok(())
+    }
+
+    pub fn malformed(&mut self, line: usize, reason: impl Into<String>) -> Result<(), MiceError> {
+        let err = self.error(line, reason);
+        self.skip(err)
+    }
+
+    pub fn summarize(&self) {
+        if self.skipped.is_empty() {
+            return;
+        }
+        eprintln!(
+            "Warning: skipped {} malformed records in {}",
+            self.skipped.len(),
+            self.file
+        );
+        for err in self.skipped.iter().take(Self::MAX_SHOWN) {
+            eprintln!("  {err}");
+        }
+        if self.skipped.len() > Self::MAX_SHOWN {
+            eprintln!("  ... and {} more", self.skipped.len() - Self::MAX_SHOWN);
+        }
+    }
+}
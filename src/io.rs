@@ -1,13 +1,23 @@
 use anyhow::{bail, Result};
-use crate::collections::{HashMap, HashSet};
+use crate::collections::{HashMap, HashSet, IndexMap, IndexMapEntry};
+use crate::config::{GenomeRule, MiceConfig, StreamedOutput};
+use crate::error::{MiceError, ParseReport};
+use crate::synteny::{SyntenyLevel, SyntenyResult};
 use std::path;
-use std::str;
 use std::fs::File;
-use std::io::{Read, BufReader,BufWriter, Write};
+use std::io::{Read, BufRead, BufReader,BufWriter, Write};
 use flate2::read::MultiGzDecoder;
+use ruzstd::decoding::{FrameDecoder, StreamingDecoder};
 
+mod alignment;
+mod block_paths;
+mod manifest;
+mod fasta;
 mod gfa;
+mod gfa2;
+mod gene_order;
 mod gff;
+mod orthogroups;
 
 //numbers convention for node_to_part array
 pub const UNINITIALIZED: usize = usize::MAX;
@@ -21,6 +31,7 @@ pub struct SignedId {
 
 pub type Path = Vec<SignedId>;
 
+#[derive(Clone)]
 pub struct NodeIndexer {
     pub map: HashMap<Vec<u8>, usize>,
     pub next: usize,
@@ -45,23 +56,206 @@ impl NodeIndexer {
 }
 
 pub struct GenomeBundle {
-    pub genomes: HashMap<String, PathBundle>,
+    pub genomes: IndexMap<String, PathBundle>,
     pub num_paths: usize,
     pub node_indexer: Option<NodeIndexer>,
 }
 
+#[derive(Default)]
 pub struct PathBundle {
     pub paths: Vec<Path>,
     pub path_names: Vec<String>,
     pub path_starts: Vec<Vec<usize>>,
     pub path_ends: Vec<Vec<usize>>,
     pub path_sizes: Vec<usize>,
+    // Circular paths, empty if every path is linear
+    pub path_circular: Vec<bool>,
+    // Short insertions (start, end) contracted out of each path, empty if none
+    pub path_insertions: Vec<Vec<(usize, usize)>>,
+}
+
+impl GenomeBundle {
+    // Genomes, and paths in each genome, sorted by name instead of first appearance
+    pub fn sort_by_name(&mut self) {
+        self.genomes.sort_keys();
+        for genome in self.genomes.values_mut() {
+            genome.sort_by_name();
+        }
+    }
+}
+
+impl PathBundle {
+    // One bundle per path named genome#path, for paths that are not grouped by genome
+    pub fn split_by_path(self, genome_name: &str) -> Vec<(String, PathBundle)> {
+        let n = self.paths.len();
+        // Values of an optional field, None for every path if the field is missing
+        fn column<T>(values: Vec<T>, n: usize) -> Vec<Option<T>> {
+            if values.len() == n {
+                values.into_iter().map(Some).collect()
+            } else {
+                std::iter::repeat_with(|| None).take(n).collect()
+            }
+        }
+        let mut starts = column(self.path_starts, n);
+        let mut ends = column(self.path_ends, n);
+        let sizes = column(self.path_sizes, n);
+        let circular = column(self.path_circular, n);
+        let mut insertions = column(self.path_insertions, n);
+
+        self.paths
+            .into_iter()
+            .zip(self.path_names)
+            .enumerate()
+            .map(|(i, (path, path_name))| {
+                let single = PathBundle {
+                    paths: vec![path],
+                    path_names: vec![path_name.clone()],
+                    path_starts: starts[i].take().into_iter().collect(),
+                    path_ends: ends[i].take().into_iter().collect(),
+                    path_sizes: sizes[i].into_iter().collect(),
+                    path_circular: circular[i].into_iter().collect(),
+                    path_insertions: insertions[i].take().into_iter().collect(),
+                };
+                (format!("{genome_name}#{path_name}"), single)
+            })
+            .collect()
+    }
+
+    // Adds the paths of other, coordinates and sizes are kept only if both bundles have them
+    pub fn append(&mut self, other: PathBundle) {
+        let (n, m) = (self.paths.len(), other.paths.len());
+        fn merge<T>(a: &mut Vec<T>, b: Vec<T>, n: usize, m: usize) {
+            if a.len() == n && b.len() == m {
+                a.extend(b);
+            } else {
+                a.clear();
+            }
+        }
+        merge(&mut self.path_starts, other.path_starts, n, m);
+        merge(&mut self.path_ends, other.path_ends, n, m);
+        merge(&mut self.path_sizes, other.path_sizes, n, m);
+        merge(&mut self.path_insertions, other.path_insertions, n, m);
+        // A missing circular flag is a linear path
+        let mut other_circular = other.path_circular;
+        if !self.path_circular.is_empty() || !other_circular.is_empty() {
+            self.path_circular.resize(n, false);
+            other_circular.resize(m, false);
+            self.path_circular.extend(other_circular);
+        }
+        self.paths.extend(other.paths);
+        self.path_names.extend(other.path_names);
+    }
+
+    #[inline]
+    pub fn is_circular(&self, i: usize) -> bool {
+        self.path_circular.get(i).copied().unwrap_or(false)
+    }
+
+    // Marks the paths with one of these names as circular
+    pub fn set_circular(&mut self, names: &HashSet<&str>) {
+        for (i, name) in self.path_names.iter().enumerate() {
+            if names.contains(name.as_str()) {
+                self.path_circular.resize(self.paths.len(), false);
+                self.path_circular[i] = true;
+            }
+        }
+    }
+
+    // Contracts the short runs of elements whose two flanking elements are adjacent in
+    // another genome, e.g. a transposon inserted in one genome: x a [t] b y against x a b y.
+    // `adjacencies` counts the genomes of each adjacency, `own` holds the adjacencies of this genome.
+    // A run is at most max_gap elements and max_gap_bp base pairs long (0 = no bound).
+    // Returns the number of contracted runs.
+    pub fn contract_insertions(
+        &mut self,
+        adjacencies: &HashMap<(usize, usize), usize>,
+        own: &HashSet<(usize, usize)>,
+        max_gap: usize,
+        max_gap_bp: usize,
+    ) -> usize {
+        let has_coordinates = self.path_starts.len() == self.paths.len();
+        let mut num_insertions = 0usize;
+        let mut path_insertions = Vec::with_capacity(self.paths.len());
+        for p in 0..self.paths.len() {
+            let path = &self.paths[p];
+            let mut kept: Vec<usize> = Vec::with_capacity(path.len());
+            let mut insertions = Vec::new();
+            let mut i = 0usize;
+            while i < path.len() {
+                kept.push(i);
+                let mut next = i + 1;
+                let mut j = i + 2;
+                while j < path.len() {
+                    let gap = j - i - 1;
+                    if max_gap > 0 && gap > max_gap {
+                        break;
+                    }
+                    if max_gap_bp > 0 {
+                        let start = self.path_starts[p][i + 1];
+                        let end = self.path_ends[p][j - 1];
+                        if end + 1 - start > max_gap_bp {
+                            break;
+                        }
+                    }
+                    let adj = adjacency(path[i], path[j]);
+                    if adjacencies.get(&adj).is_some_and(|&n| n > own.contains(&adj) as usize) {
+                        if has_coordinates {
+                            insertions.push((self.path_starts[p][i + 1], self.path_ends[p][j - 1]));
+                        }
+                        num_insertions += 1;
+                        next = j;
+                        break;
+                    }
+                    j += 1;
+                }
+                i = next;
+            }
+
+            if kept.len() < path.len() {
+                self.paths[p] = kept.iter().map(|&i| self.paths[p][i]).collect();
+                if has_coordinates {
+                    self.path_starts[p] = kept.iter().map(|&i| self.path_starts[p][i]).collect();
+                    self.path_ends[p] = kept.iter().map(|&i| self.path_ends[p][i]).collect();
+                }
+            }
+            path_insertions.push(insertions);
+        }
+        if num_insertions > 0 {
+            self.path_insertions = path_insertions;
+        }
+        num_insertions
+    }
+
+    pub fn sort_by_name(&mut self) {
+        let mut idx: Vec<usize> = (0..self.paths.len()).collect();
+        idx.sort_by(|&i, &j| self.path_names[i].cmp(&self.path_names[j]));
+
+        fn permute<T>(v: &mut Vec<T>, idx: &[usize]) {
+            if v.len() == idx.len() {
+                let mut old: Vec<Option<T>> = v.drain(..).map(Some).collect();
+                v.extend(idx.iter().map(|&i| old[i].take().unwrap()));
+            }
+        }
+        permute(&mut self.paths, &idx);
+        permute(&mut self.path_names, &idx);
+        permute(&mut self.path_starts, &idx);
+        permute(&mut self.path_ends, &idx);
+        permute(&mut self.path_sizes, &idx);
+        permute(&mut self.path_circular, &idx);
+        permute(&mut self.path_insertions, &idx);
+    }
 }
 
 pub struct GraphBundle {
     pub graph: Vec<Vec<usize>>,
     pub num_nodes: usize,
     pub duplicates: HashSet<usize>,
+    /// Elements in fewer genomes than `MiceConfig::min_genomes`, compressed apart from the others
+    pub accessory: HashSet<usize>,
+    /// Name and ids of the copies of each element split by flanking context (see `MiceConfig::split_paralogs`)
+    pub paralogs: Vec<(String, Vec<usize>)>,
+    /// Number of genomes containing each element
+    pub genome_counts: Vec<usize>,
 }
 
 pub struct PartitionBundle {
@@ -69,60 +263,68 @@ pub struct PartitionBundle {
     pub num_parts: usize,
 }
 
+// k-mer size and minimizer window used to segment FASTA input into elements
+#[derive(Clone, Debug)]
+pub struct KmerParams {
+    pub k: usize,
+    pub w: usize,
+}
+
 // ---------- Public API ----------
-pub fn load_graph(
-    input: &str,
-    force_ext: Option<&str>,
-    remove_duplicates: usize,
-    group_by: bool,
-    dirty: bool,
-) -> Result<(GraphBundle, GenomeBundle, PartitionBundle)> {
-    find_graph_type(input, force_ext)?.read_graph(input, remove_duplicates, group_by, dirty)
+pub fn load_graph(config: &MiceConfig) -> Result<(GraphBundle, GenomeBundle, PartitionBundle)> {
+    find_graph_type(config)?.read_graph(config)
 }
 
 pub fn update_graph(
-    genomes: &HashMap<String, PathBundle>,
+    genomes: &IndexMap<String, PathBundle>,
     num_nodes: usize,
     node_to_part: &[usize],
 ) -> Vec<Vec<usize>> {
     gff::Gff.genomes_to_graph(genomes, num_nodes, node_to_part)
 }
 
-pub fn write_output(
-    input: &str,
-    force_ext: Option<&str>,
-    out_dir: &path::Path,
-    genomes: &HashMap<String, PathBundle>,
-    node_to_part: &[usize],
-) -> Result<()> {
-    find_graph_type(input, force_ext)?.write_graph(out_dir, genomes, node_to_part)
+pub fn write_output(config: &MiceConfig, result: &SyntenyResult) -> Result<()> {
+    find_graph_type(config)?.write_graph(&config.out_dir, result)
 }
 
-pub fn write_paths(
-    out_dir: &path::Path,
-    genomes: &HashMap<String, PathBundle>,
-    node_to_part: &[usize],
-) -> Result<()> {
+/// Writes one output to stdout, in the same format as its file in the output directory
+pub fn write_stdout(output: StreamedOutput, result: &SyntenyResult) -> Result<()> {
+    let mut writer = BufWriter::new(std::io::stdout().lock());
+    match output {
+        StreamedOutput::Paths => write_paths_to(&mut writer, result)?,
+        StreamedOutput::Partitions => write_partition_to(&mut writer, result)?,
+        StreamedOutput::BlockIds => write_block_ids_to(&mut writer, result)?,
+        StreamedOutput::Gff => {
+            if !result.has_coordinates() {
+                bail!("the block GFF needs element coordinates, which are missing from the input");
+            }
+            gff::Gff::write_gff(&mut writer, result)?
+        }
+        StreamedOutput::Gfa => gfa::Gfa::write_gfa(&mut writer, result)?,
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn write_paths(out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
     let output = out_dir.join("paths.txt");
     std::fs::remove_file(&output).ok();
     let file = File::create(output)?;
-    let mut writer = BufWriter::new(file);
+    write_paths_to(&mut BufWriter::new(file), result)
+}
 
-    for (genome_name, genome) in genomes.iter() {
-        for (i, path) in genome.paths.iter().enumerate() {
-            let path_name = &genome.path_names[i];
-            writeln!(writer, ">{}#{}", genome_name, path_name)?;
+pub fn write_paths_to(writer: &mut impl Write, result: &SyntenyResult) -> Result<()> {
+    for genome in result.genomes.iter() {
+        for path in genome.paths.iter() {
+            writeln!(writer, ">{}#{}", genome.name, path.name)?;
             let mut print_comma = false;
-            for el in path {
-                if node_to_part[el.id] == el.id {
-                    if print_comma {
-                        write!(writer, ",")?;
-                    }
-                    print_comma = true;
-                    let part = node_to_part[el.id]+1;
-                    let sign = if el.plus { '+' } else { '-' };
-                    write!(writer, "{part}{sign}")?;
+            for block in path.blocks.iter() {
+                if print_comma {
+                    write!(writer, ",")?;
                 }
+                print_comma = true;
+                let sign = if block.plus { '+' } else { '-' };
+                write!(writer, "{}{sign}", block.id)?;
             }
             writeln!(writer)?;
         }
@@ -131,56 +333,188 @@ pub fn write_paths(
     Ok(())
 }
 
-pub fn write_partition(
-    out_dir: &path::Path,
-    num_nodes: usize,
-    node_to_part: &[usize],
-    node_indexer: Option<NodeIndexer>,
-) -> Result<()> {
+pub fn write_partition(out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
     let output = out_dir.join("partitions.txt");
     std::fs::remove_file(&output).ok();
     let file = File::create(output)?;
-    let mut writer = BufWriter::new(file);
+    write_partition_to(&mut BufWriter::new(file), result)
+}
 
-    let mut id_to_node_str = vec![Vec::new(); num_nodes];
-    if let Some(node_indexer) = node_indexer {
-        for (node_str, id) in node_indexer.map {
-            id_to_node_str[id] = node_str;
+pub fn write_partition_to(writer: &mut impl Write, result: &SyntenyResult) -> Result<()> {
+    for block in result.blocks.iter() {
+        write!(writer, "{}:", block.core)?;
+        for element in block.elements.iter() {
+            write!(writer, " {}", element)?;
         }
-    } else {
-        // default 1-based index
-        id_to_node_str = (1..=num_nodes).map(|x| x.to_string().into_bytes()).collect();
+        writeln!(writer)?;
     }
 
-    let mut partition: Vec<Vec<usize>> = (0..num_nodes).map(|_| Vec::new()).collect();
-    for (id, &part) in node_to_part.iter().enumerate() {
-        if part != FILTERED && part < num_nodes {
-            partition[part].push(id);
+    Ok(())
+}
+
+// Mapping between renumbered block ids and the ids given by the compression
+pub fn write_block_ids(out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
+    let output = out_dir.join("block_ids.txt");
+    std::fs::remove_file(&output).ok();
+    let file = File::create(output)?;
+    write_block_ids_to(&mut BufWriter::new(file), result)
+}
+
+pub fn write_block_ids_to(writer: &mut impl Write, result: &SyntenyResult) -> Result<()> {
+    writeln!(writer, "#block_id\tprevious_id\tcore_element")?;
+    for block in result.blocks.iter() {
+        writeln!(writer, "{}\t{}\t{}", block.id, block.part_id, block.core)?;
+    }
+
+    Ok(())
+}
+
+// Blocks holding copies of the same split element (see `MiceConfig::split_paralogs`)
+pub fn write_paralogs(out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
+    let output = out_dir.join("paralogs.txt");
+    std::fs::remove_file(&output).ok();
+    let file = File::create(output)?;
+    write_paralogs_to(&mut BufWriter::new(file), result)
+}
+
+pub fn write_paralogs_to(writer: &mut impl Write, result: &SyntenyResult) -> Result<()> {
+    let mut paralogs: IndexMap<&str, Vec<usize>> = IndexMap::default();
+    for block in result.blocks.iter() {
+        for element in block.paralogs.iter() {
+            paralogs.entry(element.as_str()).or_default().push(block.id);
         }
     }
+    writeln!(writer, "#element\tblock_ids")?;
+    for (element, block_ids) in paralogs.iter() {
+        let block_ids: Vec<String> = block_ids.iter().map(|id| id.to_string()).collect();
+        writeln!(writer, "{element}\t{}", block_ids.join(","))?;
+    }
 
-    for (core_id, part) in partition.iter().enumerate() {
-        if !part.is_empty() {
-            write!(writer, "{}:", str::from_utf8(&id_to_node_str[core_id]).unwrap())?;
-            for &id in part {
-                write!(writer, " {}", str::from_utf8(&id_to_node_str[id]).unwrap())?;
-            }
-            writeln!(writer)?;
+    Ok(())
+}
+
+// Block hierarchy: hierarchy.txt, and the block annotation of every coarser level as output.<threshold>.gff
+pub fn write_hierarchy(out_dir: &path::Path, levels: &[SyntenyLevel]) -> Result<()> {
+    let output = out_dir.join("hierarchy.txt");
+    std::fs::remove_file(&output).ok();
+    let file = File::create(output)?;
+    write_hierarchy_to(&mut BufWriter::new(file), levels)?;
+
+    for level in levels.iter().skip(1) {
+        let output = out_dir.join(format!("output.{}.gff", level.threshold));
+        std::fs::remove_file(&output).ok();
+        let file = File::create(output)?;
+        gff::Gff::write_gff(&mut BufWriter::new(file), &level.result)?;
+    }
+
+    Ok(())
+}
+
+pub fn write_hierarchy_to(writer: &mut impl Write, levels: &[SyntenyLevel]) -> Result<()> {
+    writeln!(writer, "#threshold\tblock_id\tparent_threshold\tparent_id")?;
+    for (level, next) in levels.iter().zip(levels.iter().skip(1)) {
+        for (block, parent) in level.result.blocks.iter().zip(level.parents.iter()) {
+            let parent = parent.map_or(".".to_string(), |id| id.to_string());
+            writeln!(writer, "{}\t{}\t{}\t{parent}", level.threshold, block.id, next.threshold)?;
         }
     }
 
     Ok(())
 }
 
-pub fn bufreader_from_compressed_file(file: &str) -> BufReader<Box<dyn Read>> {
+pub fn bufreader_from_compressed_file(file: &str) -> Result<BufReader<Box<dyn Read>>, MiceError> {
     eprintln!("loading graph from {}", &file);
-    let f = std::fs::File::open(file).unwrap_or_else(|err| panic!("Error opening file {} (err: {})", &file, err));
-    let reader: Box<dyn Read> = if file.ends_with(".gz") {
+    open_compressed_file(file)
+}
+
+// Same as bufreader_from_compressed_file, without logging (e.g. to peek at a header).
+// The file "-" is stdin.
+// The compression (gzip/BGZF, zstd, bzip2, xz) is found from the magic bytes, not the extension.
+fn open_compressed_file(file: &str) -> Result<BufReader<Box<dyn Read>>, MiceError> {
+    let f: Box<dyn Read> = if file == "-" {
+        Box::new(std::io::stdin())
+    } else {
+        Box::new(File::open(file).map_err(|source| MiceError::Open {
+            file: file.to_string(),
+            source,
+        })?)
+    };
+    let mut f = BufReader::new(f);
+    let magic = f.fill_buf().map_err(|source| MiceError::Read {
+        file: file.to_string(),
+        source,
+    })?;
+
+    let reader: Box<dyn Read> = if magic.starts_with(&[0x1f, 0x8b]) {
         Box::new(MultiGzDecoder::new(f))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        let decoder = MultiZstdDecoder::new(f).map_err(|source| MiceError::Read {
+            file: file.to_string(),
+            source,
+        })?;
+        Box::new(decoder)
+    } else if magic.starts_with(b"BZh") {
+        Box::new(bzip2::bufread::MultiBzDecoder::new(f))
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Box::new(lzma_rust2::XzReader::new(f, true))
     } else {
         Box::new(f)
     };
-    BufReader::new(reader)
+    Ok(BufReader::new(reader))
+}
+
+// Zstd decoder that keeps reading the frames following the first one, like MultiGzDecoder.
+// The decoder is only None while moving on to the next frame.
+struct MultiZstdDecoder<R: BufRead> {
+    decoder: Option<StreamingDecoder<R, FrameDecoder>>,
+}
+
+impl<R: BufRead> MultiZstdDecoder<R> {
+    fn new(source: R) -> std::io::Result<Self> {
+        let decoder = StreamingDecoder::new(source).map_err(std::io::Error::other)?;
+        Ok(Self { decoder: Some(decoder) })
+    }
+}
+
+impl<R: BufRead> Read for MultiZstdDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let Some(decoder) = self.decoder.as_mut() else {
+                return Ok(0);
+            };
+            let n = decoder.read(buf)?;
+            if n > 0 || buf.is_empty() || decoder.get_mut().fill_buf()?.is_empty() {
+                return Ok(n);
+            }
+            let (source, frame_decoder) = self.decoder.take().unwrap().into_parts();
+            let next = StreamingDecoder::new_with_decoder(source, frame_decoder)
+                .map_err(std::io::Error::other)?;
+            self.decoder = Some(next);
+        }
+    }
+}
+
+// Genome named after its file, without directory and extensions, e.g. dir/GCF_000005845.2.fna.gz -> GCF_000005845.2
+pub(crate) fn genome_name_from_file(filename: &str) -> String {
+    let name = path::Path::new(filename)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(filename);
+    let name = strip_compression_ext(name);
+    match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+        _ => name.to_string(),
+    }
+}
+
+// File name without the extension of its compression, e.g. graph.gfa.zst -> graph.gfa
+pub fn strip_compression_ext(name: &str) -> &str {
+    for ext in [".gz", ".bgz", ".zst", ".zstd", ".bz2", ".xz"] {
+        if let Some(stem) = name.strip_suffix(ext) {
+            return stem;
+        }
+    }
+    name
 }
 
 //#[allow(dead_code)]
@@ -189,28 +523,221 @@ pub fn bufreader_from_compressed_file(file: &str) -> BufReader<Box<dyn Read>> {
 //}
 
 // ---------- Private ----------
-fn find_graph_type(input: &str, force_ext: Option<&str>) -> Result<Box<dyn GraphReader>> {
-    let ext = force_ext
+fn find_graph_type(config: &MiceConfig) -> Result<Box<dyn GraphReader>> {
+    if !config.genome_files.is_empty() {
+        return Ok(Box::new(manifest::Manifest::new(config)?));
+    }
+    match input_format(config)? {
+        "manifest" => Ok(Box::new(manifest::Manifest::new(config)?)),
+        "gfa" => Ok(Box::new(gfa::Gfa)),
+        "gfa2" => Ok(Box::new(gfa2::Gfa2)),
+        "gff" => Ok(Box::new(gff::Gff)),
+        "maf" => Ok(Box::new(alignment::Alignment::Maf)),
+        "xmfa" => Ok(Box::new(alignment::Alignment::Xmfa)),
+        "grimm" => Ok(Box::new(gene_order::GeneOrder::Grimm)),
+        "unimog" => Ok(Box::new(gene_order::GeneOrder::Unimog)),
+        "orthogroups" => Ok(Box::new(orthogroups::Orthogroups)),
+        "paths" => Ok(Box::new(block_paths::BlockPaths)),
+        "fasta" => Ok(Box::new(fasta::Fasta::File)),
+        "list" => Ok(Box::new(fasta::Fasta::List)),
+        other => unreachable!("unknown input format {other}"),
+    }
+}
+
+// Format of the input, from the set format or the file extension
+fn input_format(config: &MiceConfig) -> Result<&'static str> {
+    let ext = config.format.as_deref()
     .map(|s| s.to_ascii_lowercase())
     .or_else(|| {
-        let p = std::path::Path::new(input);
-        let last = p.extension()?.to_str()?.to_ascii_lowercase();
-        if last == "gz" {
-            let stem = p.file_stem()?.to_str()?.to_ascii_lowercase();
-            stem.rsplit('.').next().map(|s| s.to_string())
-        } else {
-            Some(last)
-        }
+        let p = std::path::Path::new(&config.input);
+        let name = p.file_name()?.to_str()?.to_ascii_lowercase();
+        let stem = strip_compression_ext(&name);
+        stem.rsplit('.').next().map(|s| s.to_string())
     });
 
+    // stdin cannot be read twice, its format is not sniffed
+    let sniff = config.input != "-";
     match ext.as_deref() {
-        Some("gfa") => Ok(Box::new(gfa::Gfa)),
-        Some("gff") => Ok(Box::new(gff::Gff)),
-        Some(other) => bail!("Unsupported input extension: {}", other),
-        None => bail!("Cannot infer input type (no extension). Pass force_ext."),
+        Some("manifest") => Ok("manifest"),
+        Some("gfa") if sniff && gfa2::Gfa2::is_gfa2(&config.input)? => Ok("gfa2"),
+        Some("gfa" | "gfa1") => Ok("gfa"),
+        Some("gfa2") => Ok("gfa2"),
+        Some("gff") => Ok("gff"),
+        Some("maf") => Ok("maf"),
+        Some("xmfa") => Ok("xmfa"),
+        Some("grimm") => Ok("grimm"),
+        Some("unimog" | "ug") => Ok("unimog"),
+        Some("tsv" | "orthogroups") => Ok("orthogroups"),
+        Some("txt" | "paths") => Ok("paths"),
+        Some("fa" | "fasta" | "fna" | "fas") => Ok("fasta"),
+        Some("list" | "lst" | "fofn") => Ok("list"),
+        Some(other) => Err(MiceError::UnsupportedFormat {
+            file: config.input.clone(),
+            reason: format!("unsupported input extension: {other}"),
+        }
+        .into()),
+        None => Err(MiceError::UnsupportedFormat {
+            file: config.input.clone(),
+            reason: "cannot infer input type (no extension), set the input format".to_string(),
+        }
+        .into()),
     }
 }
 
+// Adjacency between the end of a and the start of b, the same in both directions
+#[inline]
+fn adjacency(a: SignedId, b: SignedId) -> (usize, usize) {
+    let u_extremity = a.id * 2 + (a.plus as usize);
+    let v_extremity = b.id * 2 + (!b.plus as usize);
+    (u_extremity.min(v_extremity), u_extremity.max(v_extremity))
+}
+
+// Contracts the short insertions of every genome against the adjacencies of the other genomes
+fn contract_insertions(
+    genomes: &mut IndexMap<String, PathBundle>,
+    max_gap: usize,
+    max_gap_bp: usize,
+) -> Result<()> {
+    if max_gap_bp > 0 && genomes.values().any(|g| g.path_starts.len() != g.paths.len()) {
+        bail!("--max-gap-bp requires element coordinates, which are missing from the input");
+    }
+    let genome_adjacencies: Vec<HashSet<(usize, usize)>> = genomes
+        .values()
+        .map(|g| g.paths.iter().flat_map(|path| path.windows(2).map(|w| adjacency(w[0], w[1]))).collect())
+        .collect();
+    let mut adjacencies: HashMap<(usize, usize), usize> = HashMap::default();
+    for &adj in genome_adjacencies.iter().flatten() {
+        *adjacencies.entry(adj).or_insert(0) += 1;
+    }
+
+    let num_insertions: usize = genomes
+        .values_mut()
+        .zip(genome_adjacencies.iter())
+        .map(|(genome, own)| genome.contract_insertions(&adjacencies, own, max_gap, max_gap_bp))
+        .sum();
+    eprintln!("num contracted insertions:\t{num_insertions}");
+    Ok(())
+}
+
+// Neighbours of the occurrence j of a path, read along the element (None at a linear path end)
+fn flanking_context(path: &[SignedId], j: usize, circular: bool) -> (Option<SignedId>, Option<SignedId>) {
+    let n = path.len();
+    let prev = if j > 0 { Some(path[j - 1]) } else if circular { Some(path[n - 1]) } else { None };
+    let next = if j + 1 < n { Some(path[j + 1]) } else if circular { Some(path[0]) } else { None };
+    let reverse = |sid: SignedId| SignedId { id: sid.id, plus: !sid.plus };
+    if path[j].plus {
+        (prev, next)
+    } else {
+        (next.map(reverse), prev.map(reverse))
+    }
+}
+
+// Splits the copies of an element occurring more than once in a genome into one element per
+// flanking context, so that each copy can join the block of its own neighbours. Only the genomes
+// holding several copies are split: the element keeps the most frequent context of its single
+// copies in the other genomes (or its first context), the other contexts get a new element named
+// `<name>.copy<k>`. Tandem copies (x d d y) differ by context and are split, while copies with the
+// same context in a genome (x d y ... x d y) remain duplicates.
+// Returns the name and ids of the copies of each split element.
+fn split_paralogs(genome_bundle: &mut GenomeBundle, num_nodes: &mut usize) -> Vec<(String, Vec<usize>)> {
+    // Elements with several copies in each genome
+    let duplicated_in: Vec<HashSet<usize>> = genome_bundle
+        .genomes
+        .values()
+        .map(|genome| {
+            let mut in_genome: HashSet<usize> = HashSet::default();
+            genome.paths.iter().flatten().filter(|sid| !in_genome.insert(sid.id)).map(|sid| sid.id).collect()
+        })
+        .collect();
+    let duplicated: HashSet<usize> = duplicated_in.iter().flatten().copied().collect();
+
+    // Contexts of the single copies of the duplicated elements, counted
+    type Context = (Option<SignedId>, Option<SignedId>);
+    let mut single_contexts: IndexMap<usize, IndexMap<Context, usize>> = IndexMap::default();
+    for (genome, duplicated_in_genome) in genome_bundle.genomes.values().zip(duplicated_in.iter()) {
+        for (i, path) in genome.paths.iter().enumerate() {
+            for (j, sid) in path.iter().enumerate() {
+                if duplicated.contains(&sid.id) && !duplicated_in_genome.contains(&sid.id) {
+                    let context = flanking_context(path, j, genome.is_circular(i));
+                    *single_contexts.entry(sid.id).or_default().entry(context).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    // default 1-based index
+    let mut node_indexer = genome_bundle.node_indexer.take().unwrap_or_else(|| {
+        let mut node_indexer = NodeIndexer::new();
+        for x in 1..=*num_nodes {
+            node_indexer.id_for(x.to_string().as_bytes());
+        }
+        node_indexer
+    });
+    let mut names = vec![Vec::new(); *num_nodes];
+    for (name, &id) in node_indexer.map.iter() {
+        names[id] = name.clone();
+    }
+
+    // Copy ids of each context, the element keeping the most frequent context of its single copies
+    let mut copies: IndexMap<usize, IndexMap<Context, usize>> = IndexMap::default();
+    for (&id, contexts) in single_contexts.iter() {
+        // first of the most frequent contexts
+        if let Some((&context, _)) = contexts.iter().rev().max_by_key(|(_, &count)| count) {
+            copies.entry(id).or_default().insert(context, id);
+        }
+    }
+    let mut new_ids: Vec<Vec<Vec<usize>>> = Vec::with_capacity(genome_bundle.genomes.len());
+    for (genome, duplicated_in_genome) in genome_bundle.genomes.values().zip(duplicated_in.iter()) {
+        let mut genome_ids = Vec::with_capacity(genome.paths.len());
+        for (i, path) in genome.paths.iter().enumerate() {
+            let mut path_ids = Vec::with_capacity(path.len());
+            for (j, sid) in path.iter().enumerate() {
+                if !duplicated_in_genome.contains(&sid.id) {
+                    path_ids.push(sid.id);
+                    continue;
+                }
+                let contexts = copies.entry(sid.id).or_default();
+                let num_copies = contexts.len();
+                let id = *contexts.entry(flanking_context(path, j, genome.is_circular(i))).or_insert_with(|| {
+                    if num_copies == 0 {
+                        return sid.id;
+                    }
+                    let mut k = num_copies + 1;
+                    let mut name = [names[sid.id].as_slice(), format!(".copy{k}").as_bytes()].concat();
+                    while node_indexer.map.contains_key(&name) {
+                        k += 1;
+                        name = [names[sid.id].as_slice(), format!(".copy{k}").as_bytes()].concat();
+                    }
+                    node_indexer.id_for(&name)
+                });
+                path_ids.push(id);
+            }
+            genome_ids.push(path_ids);
+        }
+        new_ids.push(genome_ids);
+    }
+
+    for (genome, genome_ids) in genome_bundle.genomes.values_mut().zip(new_ids) {
+        for (path, path_ids) in genome.paths.iter_mut().zip(genome_ids) {
+            for (sid, id) in path.iter_mut().zip(path_ids) {
+                sid.id = id;
+            }
+        }
+    }
+
+    let paralogs: Vec<(String, Vec<usize>)> = copies
+        .into_iter()
+        .filter(|(_, contexts)| contexts.len() > 1)
+        .map(|(id, contexts)| (String::from_utf8_lossy(&names[id]).into_owned(), contexts.into_values().collect()))
+        .collect();
+    eprintln!("num split paralogs:\t{}", paralogs.len());
+    eprintln!("num paralog copies:\t{}", node_indexer.next - *num_nodes);
+
+    *num_nodes = node_indexer.next;
+    genome_bundle.node_indexer = Some(node_indexer);
+    paralogs
+}
+
 // ---------- GraphReader ----------
 #[inline]
 fn add_edge_to_graph(
@@ -227,18 +754,18 @@ fn add_edge_to_graph(
 }
 
 pub trait GraphReader {
-    fn read_paths(&self, input: &str, group_by: bool) -> Result<(GenomeBundle, usize)>;
+    fn read_paths(&self, config: &MiceConfig) -> Result<(GenomeBundle, usize)>;
 
-    fn write_graph(
-        &self,
-        out_dir: &path::Path,
-        genomes: &HashMap<String, PathBundle>,
-        node_to_part: &[usize],
-    ) -> Result<()>;
+    // Element names mean the same in every file (e.g. segment names, IDs), so files can be merged
+    fn global_element_names(&self) -> bool {
+        true
+    }
+
+    fn write_graph(&self, out_dir: &path::Path, result: &SyntenyResult) -> Result<()>;
 
     fn genomes_to_graph(
         &self,
-        genomes: &HashMap<String, PathBundle>,
+        genomes: &IndexMap<String, PathBundle>,
         num_nodes: usize,
         node_to_part: &[usize],
     ) -> Vec<Vec<usize>> {
@@ -248,14 +775,19 @@ pub trait GraphReader {
         let telomer = graph.len() - 1;
 
         for (_, genome) in genomes.iter() {
-            for path in genome.paths.iter() {
+            for (path_idx, path) in genome.paths.iter().enumerate() {
                 let mut i = 0;
                 while i < path.len() && node_to_part[path[i].id] != path[i].id {
                     i += 1;
                 }
                 if i < path.len() {
                     let mut v_extremity = path[i].id * 2 + (!path[i].plus as usize);
-                    add_edge_to_graph(telomer, v_extremity, &mut graph, &mut edge_set);
+                    // A circular path closes on its first element instead of the telomer
+                    let first_extremity = v_extremity;
+                    let circular = genome.is_circular(path_idx);
+                    if !circular {
+                        add_edge_to_graph(telomer, v_extremity, &mut graph, &mut edge_set);
+                    }
                     let mut u_extremity = path[i].id * 2 + (path[i].plus as usize);
                     while i + 1 < path.len() {
                         i += 1;
@@ -266,7 +798,11 @@ pub trait GraphReader {
                         add_edge_to_graph(u_extremity, v_extremity, &mut graph, &mut edge_set);
                         u_extremity = path[i].id * 2 + (path[i].plus as usize);
                     }
-                    add_edge_to_graph(u_extremity, telomer, &mut graph, &mut edge_set);
+                    if circular {
+                        add_edge_to_graph(u_extremity, first_extremity, &mut graph, &mut edge_set);
+                    } else {
+                        add_edge_to_graph(u_extremity, telomer, &mut graph, &mut edge_set);
+                    }
                 }
             }
         }
@@ -274,18 +810,23 @@ pub trait GraphReader {
         graph
     }
 
+    // Duplicated elements (kept, filtered) and the number of genomes containing each element
     fn get_genome_duplicates(
         &self,
-        genomes: &HashMap<String, PathBundle>,
+        genomes: &IndexMap<String, PathBundle>,
+        num_nodes: usize,
         remove_duplicates: usize,
-    ) -> (HashSet<usize>, HashSet<usize>) {
+    ) -> (HashSet<usize>, HashSet<usize>, Vec<usize>) {
+        let mut genome_counts = vec![0usize; num_nodes];
         if remove_duplicates == 0 || remove_duplicates == 2 {
             let mut duplicates: HashSet<usize> = HashSet::default();
             for genome in genomes.values() {
                 let mut duplicates_in_genome: HashSet<usize> = HashSet::default();
                 for path in genome.paths.iter() {
                     for &sid in path.iter() {
-                        if !duplicates_in_genome.insert(sid.id) {
+                        if duplicates_in_genome.insert(sid.id) {
+                            genome_counts[sid.id] += 1;
+                        } else {
                             duplicates.insert(sid.id);
                         }
                     }
@@ -295,10 +836,10 @@ pub trait GraphReader {
             eprintln!("num duplicates:\t{}", duplicates.len());
 
             if remove_duplicates == 0 {
-                (duplicates, HashSet::default())
+                (duplicates, HashSet::default(), genome_counts)
             } else {
                 eprintln!("num of filtered duplicates:\t{}", duplicates.len());
-                (HashSet::default(), duplicates)
+                (HashSet::default(), duplicates, genome_counts)
             }
         } else if remove_duplicates >= 3 {
             let mut duplicates: HashSet<usize> = HashSet::default();
@@ -311,6 +852,7 @@ pub trait GraphReader {
                     }
                 }
                 for (&id, &count) in count_in_genome.iter() {
+                    genome_counts[id] += 1;
                     if count > 1 {
                         if count >= remove_duplicates {
                             duplicates_to_filter.insert(id);
@@ -329,23 +871,36 @@ pub trait GraphReader {
                 "num of filtered duplicates:\t{}",
                 duplicates_to_filter.len()
             );
-            (duplicates, duplicates_to_filter)
+            (duplicates, duplicates_to_filter, genome_counts)
         } else {
             unreachable!("Error, --remove-dup cannot be 1");
         }
     }
 
-    fn read_graph(
-        &self,
-        input: &str,
-        remove_duplicates: usize,
-        group_by: bool,
-        dirty: bool,
-    ) -> Result<(GraphBundle, GenomeBundle, PartitionBundle)> {
-        let (genome_bundle, num_nodes) = self.read_paths(input, group_by)?;
+    fn read_graph(&self, config: &MiceConfig) -> Result<(GraphBundle, GenomeBundle, PartitionBundle)> {
+        let remove_duplicates = config.remove_duplicates;
+        let dirty = config.dirty;
+        let (mut genome_bundle, mut num_nodes) = self.read_paths(config)?;
+        if !config.circular_paths.is_empty() {
+            let names: HashSet<&str> = config.circular_paths.iter().map(|name| name.as_str()).collect();
+            for genome in genome_bundle.genomes.values_mut() {
+                genome.set_circular(&names);
+            }
+        }
+        if config.sort {
+            genome_bundle.sort_by_name();
+        }
+        if config.max_gap > 0 || config.max_gap_bp > 0 {
+            contract_insertions(&mut genome_bundle.genomes, config.max_gap, config.max_gap_bp)?;
+        }
+        let paralogs = if config.split_paralogs {
+            split_paralogs(&mut genome_bundle, &mut num_nodes)
+        } else {
+            Vec::new()
+        };
 
-        let (mut duplicates, duplicates_to_filter) =
-            self.get_genome_duplicates(&genome_bundle.genomes, remove_duplicates);
+        let (mut duplicates, duplicates_to_filter, genome_counts) =
+            self.get_genome_duplicates(&genome_bundle.genomes, num_nodes, remove_duplicates);
 
         if dirty {
             duplicates = HashSet::default();
@@ -359,6 +914,23 @@ pub trait GraphReader {
                 node_to_part[el] = FILTERED;
             }
         }
+        // Elements left in no path (e.g. contracted as insertions everywhere) are no block
+        for el in (0..num_nodes).filter(|&el| genome_counts[el] == 0) {
+            node_to_part[el] = FILTERED;
+        }
+
+        let mut accessory: HashSet<usize> = HashSet::default();
+        if let Some(min_genomes) = config.min_genomes {
+            let min_count = min_genomes.count(genome_bundle.genomes.len());
+            accessory.extend((0..num_nodes).filter(|&el| genome_counts[el] > 0 && genome_counts[el] < min_count));
+            eprintln!("num accessory elements:\t{}", accessory.len());
+            if !config.compress_accessory {
+                for &el in accessory.iter() {
+                    node_to_part[el] = FILTERED;
+                }
+                accessory.clear();
+            }
+        }
 
         let graph = self.genomes_to_graph(&genome_bundle.genomes, num_nodes, &node_to_part);
 
@@ -367,6 +939,9 @@ pub trait GraphReader {
                 graph,
                 num_nodes,
                 duplicates,
+                accessory,
+                paralogs,
+                genome_counts,
             },
             genome_bundle,
             PartitionBundle {
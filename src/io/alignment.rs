@@ -0,0 +1,277 @@
+use crate::io::*;
+use anyhow::{bail, Context, Result};
+use std::io::BufRead;
+use std::str;
+
+//Multiple alignments, each alignment block is an element (named by its 1-based index in the file)
+//and each row of a block is an occurrence at its coordinates in a genome.
+//MAF (SibeliaZ, Cactus), the genome is the source name before the first '.' (or set by the genome rule):
+//a score=0
+//s Genome1.chr1  100 50 + 5000 ACGT...
+//XMFA (progressiveMauve), the genome is the sequence file, a block ends with '=':
+//> 1:101-150 + genome1.fasta
+//ACGT...
+//=
+pub enum Alignment {
+    Maf,
+    Xmfa,
+}
+
+// An occurrence of an alignment block, 1-based inclusive coordinates on the forward strand
+struct AlignedRow {
+    genome_name: String,
+    path_name: String,
+    path_size: Option<usize>,
+    start: usize,
+    end: usize,
+    plus: bool,
+}
+
+struct BoundedPath {
+    genome_name: String,
+    path: Path,
+    path_starts: Vec<usize>,
+    path_ends: Vec<usize>,
+    path_size: Option<usize>,
+}
+
+impl GraphReader for Alignment {
+    fn read_paths(&self, config: &MiceConfig) -> Result<(GenomeBundle, usize)> {
+        let parsed = match self {
+            Alignment::Maf => {
+                Self::parse_maf_paths(&config.input, config.group_by, config.genome_rule.as_ref(), config.strict)
+                    .context("Error reading MAF")
+            }
+            Alignment::Xmfa => Self::parse_xmfa_paths(&config.input, config.group_by, config.strict)
+                .context("Error reading XMFA"),
+        };
+        let (genome_bundle, num_nodes) = parsed?;
+
+        Ok((genome_bundle, num_nodes))
+    }
+
+    // Alignment blocks are numbered in reading order
+    fn global_element_names(&self) -> bool {
+        false
+    }
+
+    fn write_graph(&self, out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
+        super::gff::Gff.write_graph(out_dir, result)
+    }
+}
+
+impl Alignment {
+    // s-line (MAF):
+    // s  src  start  size  strand  src_size  text
+    // start is 0-based, on the reverse strand if strand is '-'
+    fn parse_maf_row(line: &[u8]) -> Result<AlignedRow, String> {
+        let line = str::from_utf8(line).map_err(|_| "line is not valid UTF-8".to_string())?;
+        let mut f = line.split_ascii_whitespace();
+        let mut next_field = |name: &str| f.next().ok_or_else(|| format!("s-line without {name} field"));
+        let number = |field: &str, name: &str| {
+            field
+                .parse::<usize>()
+                .map_err(|_| format!("invalid {name} '{field}'"))
+        };
+
+        let _s = next_field("record type")?;
+        let src = next_field("src")?;
+        let start = number(next_field("start")?, "start")?;
+        let size = number(next_field("size")?, "size")?;
+        let plus = match next_field("strand")? {
+            "+" => true,
+            "-" => false,
+            other => return Err(format!("invalid strand '{other}'")),
+        };
+        let src_size = number(next_field("srcSize")?, "srcSize")?;
+        if start + size > src_size {
+            return Err(format!("row {src}:{start}+{size} ends after the sequence ({src_size})"));
+        }
+        let start = if plus { start } else { src_size - start - size };
+
+        let genome_name = src.split_once('.').map_or(src, |(genome, _)| genome);
+        Ok(AlignedRow {
+            genome_name: genome_name.to_string(),
+            path_name: src.to_string(),
+            path_size: Some(src_size),
+            start: start + 1,
+            end: start + size,
+            plus,
+        })
+    }
+
+    // Row header (XMFA):
+    // > seq_index:start-end strand [sequence file]
+    // a sequence absent from the block has start and end 0
+    fn parse_xmfa_row(line: &[u8], sequence_files: &HashMap<usize, String>) -> Result<Option<AlignedRow>, String> {
+        let line = str::from_utf8(&line[1..]).map_err(|_| "line is not valid UTF-8".to_string())?;
+        let mut f = line.split_ascii_whitespace();
+        let location = f.next().ok_or("row header without location")?;
+        let strand = f.next().ok_or("row header without strand")?;
+        let file = f.next();
+
+        let invalid = || format!("invalid location '{location}'");
+        let (index, range) = location.split_once(':').ok_or_else(invalid)?;
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let index = index.parse::<usize>().map_err(|_| invalid())?;
+        let start = start.parse::<usize>().map_err(|_| invalid())?;
+        let end = end.parse::<usize>().map_err(|_| invalid())?;
+        let plus = match strand {
+            "+" => true,
+            "-" => false,
+            other => return Err(format!("invalid strand '{other}'")),
+        };
+        if start == 0 || end == 0 {
+            return Ok(None);
+        }
+
+        let file = file
+            .map(|f| f.to_string())
+            .or_else(|| sequence_files.get(&index).cloned());
+        let genome_name = match file {
+            Some(file) => genome_name_from_file(&file),
+            None => index.to_string(),
+        };
+        Ok(Some(AlignedRow {
+            path_name: genome_name.clone(),
+            genome_name,
+            path_size: None,
+            start: start.min(end),
+            end: start.max(end),
+            plus,
+        }))
+    }
+
+    fn add_row(bounded_paths: &mut IndexMap<String, BoundedPath>, row: AlignedRow, block: usize, group_by: bool) {
+        let AlignedRow { genome_name, path_name, path_size, start, end, plus } = row;
+        let p = bounded_paths.entry(path_name.clone()).or_insert_with(|| BoundedPath {
+            genome_name: if group_by { genome_name } else { path_name },
+            path: Vec::new(),
+            path_starts: Vec::new(),
+            path_ends: Vec::new(),
+            path_size,
+        });
+        p.path.push(SignedId { id: block, plus });
+        p.path_starts.push(start);
+        p.path_ends.push(end);
+    }
+
+    pub fn parse_maf_paths(
+        filename: &str,
+        group_by: bool,
+        genome_rule: Option<&GenomeRule>,
+        strict: bool,
+    ) -> Result<(GenomeBundle, usize)> {
+        if let Some(GenomeRule::Attribute(attribute)) = genome_rule {
+            bail!("the genome of a MAF row cannot be read from an attribute ({attribute}), use a delimiter, a regex or a map");
+        }
+        let mut reader = bufreader_from_compressed_file(filename)?;
+        let mut report = ParseReport::new(filename, strict);
+
+        let mut bounded_paths: IndexMap<String, BoundedPath> = IndexMap::default();
+        let mut num_blocks = 0usize;
+
+        let mut buf = vec![];
+        let mut line_num = 0usize;
+        while reader.read_until(b'\n', &mut buf).map_err(|e| report.read_error(e))? > 0 {
+            line_num += 1;
+            match buf[0] {
+                b'a' => num_blocks += 1,
+                b's' if num_blocks == 0 => report.malformed(line_num, "s-line before the first a-line")?,
+                b's' => {
+                    let row = Self::parse_maf_row(buf.trim_ascii_end()).and_then(|mut row| {
+                        let default = || std::mem::take(&mut row.genome_name);
+                        row.genome_name = super::gfa::Gfa::genome_of_path(&row.path_name, default, genome_rule)?;
+                        Ok(row)
+                    });
+                    match row {
+                        Ok(row) => Self::add_row(&mut bounded_paths, row, num_blocks - 1, group_by),
+                        Err(reason) => report.malformed(line_num, reason)?,
+                    }
+                }
+                _ => {}
+            }
+            buf.clear();
+        }
+        report.summarize();
+
+        Ok(Self::genome_bundle(bounded_paths, num_blocks))
+    }
+
+    pub fn parse_xmfa_paths(filename: &str, group_by: bool, strict: bool) -> Result<(GenomeBundle, usize)> {
+        let mut reader = bufreader_from_compressed_file(filename)?;
+        let mut report = ParseReport::new(filename, strict);
+
+        let mut bounded_paths: IndexMap<String, BoundedPath> = IndexMap::default();
+        // #SequenceNFile headers
+        let mut sequence_files: HashMap<usize, String> = HashMap::default();
+        let mut num_blocks = 0usize;
+        let mut block_rows = 0usize;
+
+        let mut buf = vec![];
+        let mut line_num = 0usize;
+        while reader.read_until(b'\n', &mut buf).map_err(|e| report.read_error(e))? > 0 {
+            line_num += 1;
+            let line = buf.trim_ascii_end();
+            if line.starts_with(b">") {
+                match Self::parse_xmfa_row(line, &sequence_files) {
+                    Ok(Some(row)) => {
+                        Self::add_row(&mut bounded_paths, row, num_blocks, group_by);
+                        block_rows += 1;
+                    }
+                    Ok(None) => {}
+                    Err(reason) => report.malformed(line_num, reason)?,
+                }
+            } else if line.starts_with(b"=") {
+                if block_rows > 0 {
+                    num_blocks += 1;
+                }
+                block_rows = 0;
+            } else if let Some(header) = line.strip_prefix(b"#Sequence") {
+                let header = String::from_utf8_lossy(header);
+                if let Some((index, file)) = header.split_once("File") {
+                    if let Ok(index) = index.parse::<usize>() {
+                        sequence_files.insert(index, file.trim().to_string());
+                    }
+                }
+            }
+            buf.clear();
+        }
+        // Last block without '='
+        if block_rows > 0 {
+            num_blocks += 1;
+        }
+        report.summarize();
+
+        Ok(Self::genome_bundle(bounded_paths, num_blocks))
+    }
+
+    // Paths sorted by position, the size of a path is its last position if not given
+    fn genome_bundle(bounded_paths: IndexMap<String, BoundedPath>, num_blocks: usize) -> (GenomeBundle, usize) {
+        let num_paths = bounded_paths.len();
+        let mut genomes: IndexMap<String, PathBundle> = IndexMap::default();
+        for (path_name, bounded_path) in bounded_paths {
+            let mut idx: Vec<usize> = (0..bounded_path.path.len()).collect();
+            idx.sort_by_key(|&i| (bounded_path.path_starts[i], bounded_path.path_ends[i]));
+
+            let path_ends: Vec<usize> = idx.iter().map(|&i| bounded_path.path_ends[i]).collect();
+            let path_size = bounded_path
+                .path_size
+                .or_else(|| path_ends.iter().max().copied())
+                .unwrap_or(0);
+            let genome = genomes.entry(bounded_path.genome_name).or_default();
+            genome.paths.push(idx.iter().map(|&i| bounded_path.path[i]).collect());
+            genome.path_starts.push(idx.iter().map(|&i| bounded_path.path_starts[i]).collect());
+            genome.path_ends.push(path_ends);
+            genome.path_sizes.push(path_size);
+            genome.path_names.push(path_name);
+        }
+
+        let genome_bundle = GenomeBundle {
+            genomes,
+            num_paths,
+            node_indexer: None,
+        };
+        (genome_bundle, num_blocks)
+    }
+}
@@ -0,0 +1,103 @@
+use crate::io::*;
+use anyhow::{Context, Result};
+use std::io::BufRead;
+
+//The paths.txt written by mice, to compress a result again:
+//>genome#path
+//1+,2-,3+
+//The genome is the header before the first '#', the elements are the block ids.
+pub struct BlockPaths;
+
+impl GraphReader for BlockPaths {
+    fn read_paths(&self, config: &MiceConfig) -> Result<(GenomeBundle, usize)> {
+        let (genome_bundle, num_nodes) =
+            Self::parse_block_paths(&config.input, config.group_by, config.strict)
+                .context("Error reading paths")?;
+
+        Ok((genome_bundle, num_nodes))
+    }
+
+    // Block ids are numbered per run, block 1 of two runs is not the same element
+    fn global_element_names(&self) -> bool {
+        false
+    }
+
+    // paths.txt is already the result for this input
+    fn write_graph(&self, _out_dir: &path::Path, _result: &SyntenyResult) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl BlockPaths {
+    #[inline]
+    fn parse_block(block: &[u8], node_indexer: &mut NodeIndexer) -> Result<SignedId, String> {
+        let (plus, name) = match block.split_last() {
+            Some((b'+', name)) if !name.is_empty() => (true, name),
+            Some((b'-', name)) if !name.is_empty() => (false, name),
+            _ => return Err(format!("invalid oriented block '{}'", String::from_utf8_lossy(block))),
+        };
+        Ok(SignedId { id: node_indexer.id_for(name), plus })
+    }
+
+    pub fn parse_block_paths(filename: &str, group_by: bool, strict: bool) -> Result<(GenomeBundle, usize)> {
+        let mut reader = bufreader_from_compressed_file(filename)?;
+        let mut report = ParseReport::new(filename, strict);
+
+        let mut genomes: IndexMap<String, PathBundle> = IndexMap::default();
+        let mut node_indexer = NodeIndexer::new();
+        let mut num_paths = 0usize;
+        // Genome of the last header, None before the first header
+        let mut current: Option<String> = None;
+
+        let mut buf = vec![];
+        let mut line_num = 0usize;
+        while reader.read_until(b'\n', &mut buf).map_err(|e| report.read_error(e))? > 0 {
+            line_num += 1;
+            let line = buf.trim_ascii();
+            if let Some(header) = line.strip_prefix(b">") {
+                let header = String::from_utf8_lossy(header).into_owned();
+                let (genome_name, path_name) = match header.split_once('#') {
+                    Some((genome, path)) => (genome.to_string(), path.to_string()),
+                    None => (header.clone(), header.clone()),
+                };
+                let genome_name = if group_by { genome_name } else { header };
+                let genome = genomes.entry(genome_name.clone()).or_default();
+                genome.paths.push(Path::new());
+                genome.path_names.push(path_name);
+                num_paths += 1;
+                current = Some(genome_name);
+            } else if line.is_empty() {
+                // Empty path
+            } else if let Some(genome_name) = &current {
+                if !genomes[genome_name].paths.last().unwrap().is_empty() {
+                    report.malformed(line_num, "second line of blocks for the same path")?;
+                } else {
+                    let mut path = Path::new();
+                    for block in line.split(|&c| c == b',') {
+                        match Self::parse_block(block.trim_ascii(), &mut node_indexer) {
+                            Ok(el) => path.push(el),
+                            Err(reason) => {
+                                report.malformed(line_num, reason)?;
+                                path.clear();
+                                break;
+                            }
+                        }
+                    }
+                    *genomes[genome_name].paths.last_mut().unwrap() = path;
+                }
+            } else {
+                report.malformed(line_num, "blocks before the first path header")?;
+            }
+            buf.clear();
+        }
+        report.summarize();
+
+        let num_nodes = node_indexer.next;
+        let genome_bundle = GenomeBundle {
+            genomes,
+            num_paths,
+            node_indexer: Some(node_indexer),
+        };
+        Ok((genome_bundle, num_nodes))
+    }
+}
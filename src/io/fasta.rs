@@ -0,0 +1,257 @@
+use crate::io::*;
+use anyhow::{bail, Context, Result};
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+//Genomes given as FASTA, either a single file (one genome) or a list with one FASTA per line
+//(.list, .lst, .fofn). The elements are canonical k-mers sampled as (w,k)-minimizers, each occurrence
+//is located at its position in the sequence. With w = 1 every k-mer is an element.
+pub enum Fasta {
+    File,
+    List,
+}
+
+impl GraphReader for Fasta {
+    fn read_paths(&self, config: &MiceConfig) -> Result<(GenomeBundle, usize)> {
+        let (genome_bundle, num_nodes) = Self::parse_fasta_paths(
+            &config.input,
+            matches!(self, Fasta::List),
+            &config.kmer_params,
+            config.group_by,
+            config.strict,
+        )
+        .context("Error reading FASTA")?;
+
+        Ok((genome_bundle, num_nodes))
+    }
+
+    // k-mers are numbered in reading order, several genomes are given as a list instead
+    fn global_element_names(&self) -> bool {
+        false
+    }
+
+    fn write_graph(&self, out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
+        super::gff::Gff.write_graph(out_dir, result)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Anchor {
+    pos: usize,
+    hash: u64,
+    kmer: u64,
+    plus: bool,
+}
+
+impl Fasta {
+    pub const MAX_K: usize = 32;
+
+    #[inline]
+    fn encode(base: u8) -> Option<u64> {
+        match base {
+            b'A' | b'a' => Some(0),
+            b'C' | b'c' => Some(1),
+            b'G' | b'g' => Some(2),
+            b'T' | b't' => Some(3),
+            _ => None,
+        }
+    }
+
+    // Hash used to order k-mers (splitmix64 finalizer), so minimizers are not biased to poly-A
+    #[inline]
+    fn hash(kmer: u64) -> u64 {
+        let mut x = kmer.wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    }
+
+    // Minimizers of the canonical k-mers of seq, windows do not span non-ACGT characters.
+    // Positions are 0-based starts of the k-mers.
+    fn anchors(params: &KmerParams, seq: &[u8]) -> Vec<Anchor> {
+        let k = params.k;
+        let w = params.w;
+        let mask = if k == 32 { u64::MAX } else { (1u64 << (2 * k)) - 1 };
+        let shift = 2 * (k as u64 - 1);
+
+        let mut anchors = Vec::new();
+        let mut window: VecDeque<Anchor> = VecDeque::new();
+        let mut fwd = 0u64;
+        let mut rev = 0u64;
+        let mut valid = 0usize;
+        let mut last_pos = usize::MAX;
+
+        for (i, &base) in seq.iter().enumerate() {
+            let Some(x) = Self::encode(base) else {
+                valid = 0;
+                window.clear();
+                continue;
+            };
+            fwd = ((fwd << 2) | x) & mask;
+            rev = (rev >> 2) | ((3 - x) << shift);
+            valid += 1;
+            if valid < k {
+                continue;
+            }
+
+            let pos = i + 1 - k;
+            let (kmer, plus) = if fwd <= rev { (fwd, true) } else { (rev, false) };
+            let hash = Self::hash(kmer);
+
+            while window.back().is_some_and(|a| a.hash > hash) {
+                window.pop_back();
+            }
+            window.push_back(Anchor { pos, hash, kmer, plus });
+            while window.front().is_some_and(|a| a.pos + w <= pos) {
+                window.pop_front();
+            }
+
+            if valid >= k + w - 1 {
+                let min = window.front().unwrap();
+                if min.pos != last_pos {
+                    last_pos = min.pos;
+                    anchors.push(*min);
+                }
+            }
+        }
+
+        anchors
+    }
+
+    // Each non-empty line of the list is a FASTA file, relative paths are
+    // looked up from the working directory, then from the directory of the list
+    fn read_list(filename: &str) -> Result<Vec<String>> {
+        let dir = path::Path::new(filename).parent().unwrap_or(path::Path::new(""));
+        let mut files = Vec::new();
+        let reader = bufreader_from_compressed_file(filename)?;
+        for line in reader.lines() {
+            let line = line.map_err(|source| MiceError::Read {
+                file: filename.to_string(),
+                source,
+            })?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let file = path::Path::new(line);
+            if file.exists() || file.is_absolute() {
+                files.push(line.to_string());
+            } else {
+                files.push(dir.join(file).to_string_lossy().into_owned());
+            }
+        }
+        Ok(files)
+    }
+
+    fn add_record(
+        params: &KmerParams,
+        record_name: String,
+        seq: &[u8],
+        genome: &mut PathBundle,
+        kmer_indexer: &mut HashMap<u64, usize>,
+    ) {
+        let anchors = Self::anchors(params, seq);
+        let mut path = Vec::with_capacity(anchors.len());
+        let mut path_starts = Vec::with_capacity(anchors.len());
+        let mut path_ends = Vec::with_capacity(anchors.len());
+        for anchor in anchors {
+            let next = kmer_indexer.len();
+            let id = *kmer_indexer.entry(anchor.kmer).or_insert(next);
+            path.push(SignedId { id, plus: anchor.plus });
+            path_starts.push(anchor.pos + 1);
+            path_ends.push(anchor.pos + params.k);
+        }
+        genome.paths.push(path);
+        genome.path_names.push(record_name);
+        genome.path_starts.push(path_starts);
+        genome.path_ends.push(path_ends);
+        genome.path_sizes.push(seq.len());
+    }
+
+    fn parse_fasta_file(
+        params: &KmerParams,
+        filename: &str,
+        strict: bool,
+        genome: &mut PathBundle,
+        kmer_indexer: &mut HashMap<u64, usize>,
+    ) -> Result<()> {
+        let mut reader = bufreader_from_compressed_file(filename)?;
+        let mut report = ParseReport::new(filename, strict);
+
+        let mut record_name: Option<String> = None;
+        let mut seq: Vec<u8> = Vec::new();
+        let mut buf = vec![];
+        let mut line_num = 0usize;
+        while reader.read_until(b'\n', &mut buf).map_err(|e| report.read_error(e))? > 0 {
+            line_num += 1;
+            let line = buf.trim_ascii_end();
+            if let Some(header) = line.strip_prefix(b">") {
+                if let Some(name) = record_name.take() {
+                    Self::add_record(params, name, &seq, genome, kmer_indexer);
+                }
+                let name = header.split(|c| c.is_ascii_whitespace()).next().unwrap_or(b"");
+                record_name = Some(String::from_utf8_lossy(name).into_owned());
+                seq.clear();
+            } else if record_name.is_some() {
+                seq.extend_from_slice(line);
+            } else if !line.is_empty() {
+                report.malformed(line_num, "sequence before the first FASTA header")?;
+            }
+            buf.clear();
+        }
+        if let Some(name) = record_name.take() {
+            Self::add_record(params, name, &seq, genome, kmer_indexer);
+        }
+        report.summarize();
+
+        Ok(())
+    }
+
+    pub fn parse_fasta_paths(
+        filename: &str,
+        list: bool,
+        params: &KmerParams,
+        group_by: bool,
+        strict: bool,
+    ) -> Result<(GenomeBundle, usize)> {
+        if params.k == 0 || params.k > Self::MAX_K {
+            bail!("k must be between 1 and {}", Self::MAX_K);
+        }
+        if params.w == 0 {
+            bail!("the window size must be at least 1");
+        }
+
+        let files = if list {
+            Self::read_list(filename)?
+        } else {
+            vec![filename.to_string()]
+        };
+
+        let mut genomes: IndexMap<String, PathBundle> = IndexMap::default();
+        let mut kmer_indexer: HashMap<u64, usize> = HashMap::default();
+        let mut num_paths = 0usize;
+
+        for file in files.iter() {
+            let mut genome = PathBundle::default();
+            Self::parse_fasta_file(params, file, strict, &mut genome, &mut kmer_indexer)?;
+            num_paths += genome.paths.len();
+
+            let genome_name = genome_name_from_file(file);
+            if group_by {
+                if genomes.insert(genome_name.clone(), genome).is_some() {
+                    bail!("genome {} is given twice", genome_name);
+                }
+            } else {
+                genomes.extend(genome.split_by_path(&genome_name));
+            }
+        }
+
+        let num_nodes = kmer_indexer.len();
+        let genome_bundle = GenomeBundle {
+            genomes,
+            num_paths,
+            node_indexer: None,
+        };
+        Ok((genome_bundle, num_nodes))
+    }
+}
@@ -0,0 +1,160 @@
+use crate::io::*;
+use anyhow::{Context, Result};
+use std::io::BufRead;
+use std::str;
+
+//Genomes as signed gene orders, GRIMM or UniMoG:
+//>genome1
+//1 -3 2 $      GRIMM: `$` ends a linear chromosome (`@` a circular one)
+//a -c b |      UniMoG: `|` ends a linear chromosome, `)` a circular one
+//Both markers are accepted by the reader, a line without marker is a linear chromosome.
+//Genes are names (a leading `-` is the reverse strand), there are no coordinates,
+//chromosomes are named by their 1-based index in the genome.
+pub enum GeneOrder {
+    Grimm,
+    Unimog,
+}
+
+impl GraphReader for GeneOrder {
+    fn read_paths(&self, config: &MiceConfig) -> Result<(GenomeBundle, usize)> {
+        let (genome_bundle, num_nodes) =
+            Self::parse_gene_order_paths(&config.input, config.group_by, config.strict)
+                .context("Error reading gene orders")?;
+
+        Ok((genome_bundle, num_nodes))
+    }
+
+    // The blocks orders, in the format of the input
+    fn write_graph(&self, out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
+        let (file_name, linear, circular) = match self {
+            GeneOrder::Grimm => ("output.grimm", '$', '@'),
+            GeneOrder::Unimog => ("output.unimog", '|', ')'),
+        };
+        let output = out_dir.join(file_name);
+        std::fs::remove_file(&output).ok();
+        let file = File::create(output)?;
+        let mut writer = BufWriter::new(file);
+
+        for genome in result.genomes.iter() {
+            writeln!(writer, ">{}", genome.name)?;
+            for path in genome.paths.iter() {
+                if path.blocks.is_empty() {
+                    continue;
+                }
+                for block in path.blocks.iter() {
+                    let sign = if block.plus { "" } else { "-" };
+                    write!(writer, "{sign}{} ", block.id)?;
+                }
+                writeln!(writer, "{}", if path.circular { circular } else { linear })?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl GeneOrder {
+    fn end_marker(c: u8) -> Option<bool> {
+        match c {
+            b'$' | b'|' => Some(false),
+            b'@' | b')' => Some(true),
+            _ => None,
+        }
+    }
+
+    fn push_chromosome(genome: &mut PathBundle, path: Path, circular: bool) {
+        genome.path_names.push((genome.paths.len() + 1).to_string());
+        genome.paths.push(path);
+        genome.path_circular.push(circular);
+    }
+
+    #[inline]
+    fn parse_gene(gene: &[u8], node_indexer: &mut NodeIndexer) -> Result<SignedId, String> {
+        let (plus, name) = match gene.split_first() {
+            Some((b'-', name)) => (false, name),
+            Some((b'+', name)) => (true, name),
+            _ => (true, gene),
+        };
+        if name.is_empty() {
+            return Err(format!("invalid gene '{}'", String::from_utf8_lossy(gene)));
+        }
+        Ok(SignedId { id: node_indexer.id_for(name), plus })
+    }
+
+    pub fn parse_gene_order_paths(filename: &str, group_by: bool, strict: bool) -> Result<(GenomeBundle, usize)> {
+        let mut reader = bufreader_from_compressed_file(filename)?;
+        let mut report = ParseReport::new(filename, strict);
+
+        let mut genomes: IndexMap<String, PathBundle> = IndexMap::default();
+        let mut node_indexer = NodeIndexer::new();
+        let mut current: Option<(String, PathBundle)> = None;
+        let mut path = Path::new();
+
+        let mut buf = vec![];
+        let mut line_num = 0usize;
+        while reader.read_until(b'\n', &mut buf).map_err(|e| report.read_error(e))? > 0 {
+            line_num += 1;
+            let line = buf.trim_ascii();
+            if line.is_empty() || line[0] == b'#' {
+                // Empty line or comment
+            } else if let Some(name) = line.strip_prefix(b">") {
+                if let Some((name, genome)) = current.take() {
+                    genomes.insert(name, genome);
+                }
+                let name = String::from_utf8_lossy(name.trim_ascii()).into_owned();
+                // A genome given twice gets the chromosomes of both
+                let genome = match genomes.shift_remove(&name) {
+                    Some(genome) => {
+                        report.malformed(line_num, format!("genome {name} is given twice"))?;
+                        genome
+                    }
+                    None => PathBundle::default(),
+                };
+                current = Some((name, genome));
+            } else if let Some((_, genome)) = current.as_mut() {
+                for token in line.split(|c| c.is_ascii_whitespace()).filter(|t| !t.is_empty()) {
+                    // The marker may be attached to the last gene, e.g. "3$"
+                    let (gene, marker) = match token.split_last() {
+                        Some((&c, gene)) if Self::end_marker(c).is_some() => (gene, Self::end_marker(c)),
+                        _ => (token, None),
+                    };
+                    if !gene.is_empty() {
+                        match Self::parse_gene(gene, &mut node_indexer) {
+                            Ok(el) => path.push(el),
+                            Err(reason) => report.malformed(line_num, reason)?,
+                        }
+                    }
+                    if let Some(circular) = marker {
+                        Self::push_chromosome(genome, std::mem::take(&mut path), circular);
+                    }
+                }
+                if !path.is_empty() {
+                    Self::push_chromosome(genome, std::mem::take(&mut path), false);
+                }
+            } else {
+                report.malformed(line_num, "gene order before the first genome header")?;
+            }
+            buf.clear();
+        }
+        if let Some((name, genome)) = current.take() {
+            genomes.insert(name, genome);
+        }
+        report.summarize();
+
+        if !group_by {
+            genomes = genomes
+                .into_iter()
+                .flat_map(|(genome_name, genome)| genome.split_by_path(&genome_name))
+                .collect();
+        }
+
+        let num_paths = genomes.values().map(|g| g.paths.len()).sum();
+        let num_nodes = node_indexer.next;
+        let genome_bundle = GenomeBundle {
+            genomes,
+            num_paths,
+            node_indexer: Some(node_indexer),
+        };
+        Ok((genome_bundle, num_nodes))
+    }
+}
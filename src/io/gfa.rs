@@ -1,113 +1,512 @@
 use crate::io::*;
-use anyhow::Result;
-use std::collections::hash_map;
+use crate::synteny::{BlockClass, SyntenyResult};
+use anyhow::{bail, Context, Result};
 use std::io::BufRead;
 use std::str;
 
 pub struct Gfa;
 
+// A path of a GFA (P-line, W-line, or GFA2 O-group) before its coordinates are computed
+pub(super) struct GfaPath {
+    pub(super) genome_name: String,
+    pub(super) path_name: String,
+    pub(super) path: Path,
+    pub(super) overlaps: Option<Vec<usize>>,
+    pub(super) offset: usize,
+    pub(super) circular: bool,
+}
+
 impl GraphReader for Gfa {
-    fn read_paths(&self, gfa_input: &str, group_by: bool) -> Result<(GenomeBundle, usize)> {
-        let (genome_bundle, num_nodes) = Self::parse_gfa_paths(gfa_input, group_by)
-            .map_err(|e| anyhow::anyhow!("Error reading GFA: {}", e))?;
+    fn read_paths(&self, config: &MiceConfig) -> Result<(GenomeBundle, usize)> {
+        let (genome_bundle, num_nodes) =
+            Self::parse_gfa_paths(&config.input, config.group_by, config.genome_rule.as_ref(), config.strict)
+                .context("Error reading GFA")?;
 
         Ok((genome_bundle, num_nodes))
     }
 
-    #[allow(unused_variables)]
-    //TODO
-    fn write_graph(
-        &self,
-        out_dir: &path::Path,
-        genomes: &HashMap<String, PathBundle>,
-        node_to_part: &[usize],
-    ) -> Result<()> {
+    fn write_graph(&self, out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
+        let output = out_dir.join("output.gfa");
+        std::fs::remove_file(&output).ok();
+        let file = File::create(output)?;
+        Self::write_gfa(&mut BufWriter::new(file), result)?;
+
+        // Block coordinates, projected from the segment lengths along each path
+        if result.has_coordinates() {
+            super::gff::Gff.write_graph(out_dir, result)?;
+        }
+
         Ok(())
     }
 }
 
 impl Gfa {
+    // Block graph: one segment per block, links between adjacent blocks and one path per genome path
+    pub fn write_gfa(writer: &mut impl Write, result: &SyntenyResult) -> Result<()> {
+        writeln!(writer, "H\tVN:Z:1.0")?;
+
+        //Segments, in order of first appearance in the paths, tagged with their class
+        let classes: HashMap<usize, BlockClass> = result.blocks.iter().map(|b| (b.id, b.class)).collect();
+        let mut seen_blocks: HashSet<usize> = HashSet::default();
+        for genome in result.genomes.iter() {
+            for path in genome.paths.iter() {
+                for block in path.blocks.iter() {
+                    if seen_blocks.insert(block.id) {
+                        writeln!(writer, "S\t{}\t*\tCL:Z:{}", block.id, classes[&block.id])?;
+                    }
+                }
+            }
+        }
+
+        //Links, a link and its reverse complement are the same, a circular path also links its last block to its first
+        let mut links: HashSet<(SignedId, SignedId)> = HashSet::default();
+        for genome in result.genomes.iter() {
+            for path in genome.paths.iter() {
+                let origin = path.blocks.first().filter(|_| path.circular);
+                let next_blocks = path.blocks.iter().skip(1).chain(origin);
+                for (&a, &b) in path.blocks.iter().zip(next_blocks) {
+                    let a_rev = SignedId { id: a.id, plus: !a.plus };
+                    let b_rev = SignedId { id: b.id, plus: !b.plus };
+                    if links.contains(&(b_rev, a_rev)) || !links.insert((a, b)) {
+                        continue;
+                    }
+                    writeln!(
+                        writer,
+                        "L\t{}\t{}\t{}\t{}\t0M",
+                        a.id,
+                        Self::orient_char(a.plus),
+                        b.id,
+                        Self::orient_char(b.plus)
+                    )?;
+                }
+            }
+        }
+
+        //Paths
+        for genome in result.genomes.iter() {
+            for path in genome.paths.iter() {
+                if path.blocks.is_empty() {
+                    continue;
+                }
+                write!(writer, "P\t{}\t", path.name)?;
+                for (i, block) in path.blocks.iter().enumerate() {
+                    if i > 0 {
+                        write!(writer, ",")?;
+                    }
+                    write!(writer, "{}{}", block.id, Self::orient_char(block.plus))?;
+                }
+                writeln!(writer, "\t*{}", if path.circular { "\tTP:Z:circular" } else { "" })?;
+            }
+        }
+
+        Ok(())
+    }
+
     #[inline]
-    fn path_name_to_genome_string(path_name: String) -> String {
+    fn orient_char(plus: bool) -> char {
+        if plus { '+' } else { '-' }
+    }
+
+    #[inline]
+    pub(super) fn path_name_to_genome_string(path_name: String) -> String {
         match path_name.find("#") {
             Some(idx) => path_name[..idx].to_string(),
             None => path_name,
         }
     }
 
+    // Genome of a path by the genome rule, the default genome of the line if there is no rule
+    pub(super) fn genome_of_path(
+        path_name: &str,
+        default: impl FnOnce() -> String,
+        genome_rule: Option<&GenomeRule>,
+    ) -> Result<String, String> {
+        match genome_rule {
+            None => Ok(default()),
+            Some(rule) => rule
+                .genome_from_name(path_name)
+                .ok_or_else(|| format!("no genome for path {path_name}")),
+        }
+    }
+
+    // Genome attributes only exist in GFF
+    pub(super) fn check_genome_rule(genome_rule: Option<&GenomeRule>) -> Result<()> {
+        if let Some(GenomeRule::Attribute(attribute)) = genome_rule {
+            bail!("the genome of a GFA path cannot be read from an attribute ({attribute}), use a delimiter, a regex or a map");
+        }
+        Ok(())
+    }
+
+    // Optional tags of a path, a circular path is tagged TP:Z:circular
+    pub(super) fn has_circular_tag<'a>(mut tags: impl Iterator<Item = &'a [u8]>) -> bool {
+        tags.any(|tag| tag.trim_ascii_end() == b"TP:Z:circular")
+    }
+
+    #[inline]
+    fn parse_path_node(node: &[u8], node_indexer: &mut NodeIndexer) -> Result<SignedId> {
+        let (plus, name) = match node.split_last() {
+            Some((b'+', name)) if !name.is_empty() => (true, name),
+            Some((b'-', name)) if !name.is_empty() => (false, name),
+            _ => bail!("invalid oriented segment '{}'", String::from_utf8_lossy(node)),
+        };
+        let id = node_indexer.id_for(name);
+        Ok(SignedId { id, plus })
+    }
+
     #[inline]
-    fn parse_path_node(node: &[u8], node_indexer: &mut NodeIndexer) -> SignedId {
-        let orient = *node.last().unwrap();
-        let name = &node[..node.len() - 1];
+    pub(super) fn split_fields(data: &[u8]) -> std::slice::Split<'_, u8, impl FnMut(&u8) -> bool> {
+        let data = data.strip_suffix(b"\n").unwrap_or(data);
+        let data = data.strip_suffix(b"\r").unwrap_or(data);
+        data.split(|&x| x == b'\t')
+    }
+
+    // Length of the overlap on the segment entered by a link, e.g. 30M -> 30
+    fn parse_overlap(cigar: &[u8]) -> Result<usize> {
+        if cigar == b"*" {
+            return Ok(0);
+        }
+        let mut overlap = 0usize;
+        let mut num = 0usize;
+        for &c in cigar {
+            match c {
+                b'0'..=b'9' => num = num * 10 + (c - b'0') as usize,
+                b'M' | b'I' | b'S' | b'=' | b'X' => {
+                    overlap += num;
+                    num = 0;
+                }
+                b'D' | b'N' | b'H' | b'P' => num = 0,
+                _ => bail!("invalid overlap {}", str::from_utf8(cigar)?),
+            }
+        }
+        Ok(overlap)
+    }
+
+    // S-line (GFA 1):
+    // S  name  sequence  [LN:i:length]
+    fn parse_segment_length(data: &[u8], node_indexer: &mut NodeIndexer) -> Result<(usize, Option<usize>)> {
+        let mut f = Self::split_fields(data);
+        let _s = f.next();
+        let name = f.next().ok_or_else(|| anyhow::anyhow!("S-line without name"))?;
+        let seq = f.next().ok_or_else(|| anyhow::anyhow!("S-line without sequence"))?;
         let id = node_indexer.id_for(name);
-        SignedId {
-            id,
-            plus: orient == b'+',
+        if seq != b"*" {
+            return Ok((id, Some(seq.len())));
         }
+        for tag in f {
+            if let Some(len) = tag.strip_prefix(b"LN:i:") {
+                return Ok((id, Some(str::from_utf8(len)?.parse::<usize>()?)));
+            }
+        }
+        Ok((id, None))
     }
 
+    // L-line (GFA 1):
+    // L  from  from_orient  to  to_orient  overlap
+    fn parse_link(data: &[u8], node_indexer: &mut NodeIndexer) -> Result<(SignedId, SignedId, usize)> {
+        let mut f = Self::split_fields(data);
+        let _l = f.next();
+        let mut next_field = |name: &str| {
+            f.next()
+                .ok_or_else(|| anyhow::anyhow!("L-line without {} field", name))
+        };
+        let from = node_indexer.id_for(next_field("from")?);
+        let from_plus = next_field("from orientation")? == b"+";
+        let to = node_indexer.id_for(next_field("to")?);
+        let to_plus = next_field("to orientation")? == b"+";
+        let overlap = Self::parse_overlap(next_field("overlap")?)?;
+
+        Ok((
+            SignedId { id: from, plus: from_plus },
+            SignedId { id: to, plus: to_plus },
+            overlap,
+        ))
+    }
+
+    // P-line (GFA 1):
+    // P  path_name  segment_names  overlaps  [tags]
     fn parse_path_seq_to_signed_id_vec(
         data: &[u8],
         node_indexer: &mut NodeIndexer,
-    ) -> Result<(String, Path)> {
-        let mut it = data.iter();
-        let mut start = it.position(|&x| x == b'\t').unwrap() + 1; //skip P
-        let end_name = it.position(|&x| x == b'\t').unwrap() + 1;
-
-        let path_name = str::from_utf8(&data[start..end_name + 1])?.to_owned();
-        start += end_name; //skip path_name
+    ) -> Result<(String, Path, Option<Vec<usize>>, bool)> {
+        let mut f = Self::split_fields(data);
+        let _p = f.next();
+        let mut next_field = |name: &str| {
+            f.next()
+                .ok_or_else(|| anyhow::anyhow!("P-line without {} field", name))
+        };
 
-        let data = &data[start..];
-        let end = it
-            //.position(|x| x == &b'\t' || x == &b'\n' || x == &b'\r')
-            .rposition(|x| x == &b'\t')
-            .unwrap();
+        let path_name = str::from_utf8(next_field("path name")?)?.to_owned();
 
-        let path: Vec<SignedId> = data[..end]
+        let path: Vec<SignedId> = next_field("segment names")?
             .split(|&x| x == b',')
             .map(|node| Self::parse_path_node(node, node_indexer))
-            .collect();
+            .collect::<Result<_>>()?;
+
+        let overlaps = match f.next() {
+            Some(o) if o != b"*" && !o.is_empty() => Some(
+                o.split(|&x| x == b',')
+                    .map(Self::parse_overlap)
+                    .collect::<Result<Vec<usize>>>()?,
+            ),
+            _ => None,
+        };
+
+        Ok((path_name, path, overlaps, Self::has_circular_tag(f)))
+    }
+
+    // W-line (GFA 1.1):
+    // W  sample  hap_index  seq_id  seq_start  seq_end  walk  [tags]
+    // the walk is a sequence of oriented segments, e.g. >s1<s2>s3
+    // The path name follows the PanSN convention: sample#hap_index#seq_id
+    fn parse_walk_seq_to_signed_id_vec(
+        data: &[u8],
+        node_indexer: &mut NodeIndexer,
+    ) -> Result<(String, String, Path, usize, bool)> {
+        let mut f = Self::split_fields(data);
+        let mut next_field = |name: &str| {
+            f.next()
+                .ok_or_else(|| anyhow::anyhow!("W-line without {} field", name))
+        };
+
+        let _w = next_field("record type")?;
+        let sample = str::from_utf8(next_field("sample")?)?.to_owned();
+        let hap_index = str::from_utf8(next_field("haplotype index")?)?;
+        let seq_id = str::from_utf8(next_field("sequence id")?)?;
+        let seq_start = next_field("sequence start")?;
+        let _seq_end = next_field("sequence end")?;
+        let walk = next_field("walk")?;
+
+        let path_name = format!("{sample}#{hap_index}#{seq_id}");
+        let offset = if seq_start == b"*" {
+            0
+        } else {
+            str::from_utf8(seq_start)?.parse::<usize>()?
+        };
 
-        Ok((path_name, path))
+        let mut path = Path::new();
+        let mut i = 0usize;
+        while i < walk.len() {
+            let plus = match walk[i] {
+                b'>' => true,
+                b'<' => false,
+                other => bail!(
+                    "unexpected character '{}' in walk of {}",
+                    other as char,
+                    path_name
+                ),
+            };
+            let mut j = i + 1;
+            while j < walk.len() && walk[j] != b'>' && walk[j] != b'<' {
+                j += 1;
+            }
+            if j == i + 1 {
+                bail!("empty segment name in walk of {}", path_name);
+            }
+            let id = node_indexer.id_for(&walk[i + 1..j]);
+            path.push(SignedId { id, plus });
+            i = j;
+        }
+
+        Ok((sample, path_name, path, offset, Self::has_circular_tag(f)))
+    }
+
+    // Coordinates (1-based, inclusive) of each element along the path, for P-lines, W-lines and GFA2 groups.
+    // Consecutive elements overlap by the overlap of the P-line, or of the L-line if missing.
+    // A zero-length element ends just before its start, leaving it out of the block intervals.
+    fn path_coordinates(
+        gfa_path: &GfaPath,
+        node_lengths: &[Option<usize>],
+        node_to_seg: &[usize],
+        link_overlaps: &HashMap<(SignedId, SignedId), usize>,
+    ) -> Option<(Vec<usize>, Vec<usize>)> {
+        let path = &gfa_path.path;
+        let mut starts = Vec::with_capacity(path.len());
+        let mut ends = Vec::with_capacity(path.len());
+        let mut pos = gfa_path.offset + 1;
+        for (i, el) in path.iter().enumerate() {
+            let len = node_lengths[el.id]?;
+            starts.push(pos);
+            ends.push(pos + len - 1);
+            if i + 1 < path.len() {
+                let overlap = match &gfa_path.overlaps {
+                    Some(overlaps) => overlaps.get(i).copied().unwrap_or(0),
+                    None => {
+                        let from = SignedId { id: node_to_seg[el.id], plus: el.plus };
+                        let to = SignedId { id: node_to_seg[path[i + 1].id], plus: path[i + 1].plus };
+                        link_overlaps.get(&(from, to)).copied().unwrap_or(0)
+                    }
+                };
+                pos = (pos + len).saturating_sub(overlap).max(pos);
+            }
+        }
+        Some((starts, ends))
     }
 
-    pub fn parse_gfa_paths(filename: &str, group_by: bool) -> Result<(GenomeBundle, usize)> {
-        let mut reader = bufreader_from_compressed_file(filename);
+    pub fn parse_gfa_paths(
+        filename: &str,
+        group_by: bool,
+        genome_rule: Option<&GenomeRule>,
+        strict: bool,
+    ) -> Result<(GenomeBundle, usize)> {
+        Self::check_genome_rule(genome_rule)?;
+        let mut reader = bufreader_from_compressed_file(filename)?;
+        let mut report = ParseReport::new(filename, strict);
 
-        let mut genomes: HashMap<String, PathBundle> = HashMap::default();
-        let mut num_paths = 0usize;
+        let mut gfa_paths: Vec<GfaPath> = Vec::new();
+        let mut seg_lengths: Vec<Option<usize>> = Vec::new();
+        let mut link_overlaps: HashMap<(SignedId, SignedId), usize> = HashMap::default();
         let mut node_indexer = NodeIndexer::new();
+        // Segments and links get their own index, to not count segments absent from paths
+        let mut seg_indexer = NodeIndexer::new();
+
+        // GFA2 ordered groups, only used to explain a missing path
+        let mut has_groups = false;
 
         let mut buf = vec![];
-        while reader.read_until(b'\n', &mut buf).unwrap_or(0) > 0 {
+        let mut line_num = 0usize;
+        while reader.read_until(b'\n', &mut buf).map_err(|e| report.read_error(e))? > 0 {
+            line_num += 1;
             if buf[0] == b'P' {
-                let (path_name, path) =
-                    Self::parse_path_seq_to_signed_id_vec(&buf, &mut node_indexer)?;
-                let mut genome_name = path_name.clone();
-                if group_by {
-                    genome_name = Self::path_name_to_genome_string(genome_name);
-                }
+                match Self::parse_path_seq_to_signed_id_vec(&buf, &mut node_indexer) {
+                    Ok((path_name, path, overlaps, circular)) => {
+                        let genome_name = if group_by {
+                            let default = || Self::path_name_to_genome_string(path_name.clone());
+                            match Self::genome_of_path(&path_name, default, genome_rule) {
+                                Ok(genome_name) => genome_name,
+                                Err(reason) => {
+                                    report.malformed(line_num, reason)?;
+                                    buf.clear();
+                                    continue;
+                                }
+                            }
+                        } else {
+                            path_name.clone()
+                        };
 
-                let entry = genomes.entry(genome_name);
-                match entry {
-                    hash_map::Entry::Occupied(mut p) => {
-                        p.get_mut().paths.push(path);
-                        p.get_mut().path_names.push(path_name);
+                        gfa_paths.push(GfaPath {
+                            genome_name,
+                            path_name,
+                            path,
+                            overlaps,
+                            offset: 0,
+                            circular,
+                        });
                     }
-                    hash_map::Entry::Vacant(p) => {
-                        p.insert(PathBundle {
-                            paths: vec![path],
-                            path_names: vec![path_name],
-                            path_starts: Vec::new(),
-                            path_ends: Vec::new(),
-                            path_sizes: Vec::new(),
+                    Err(e) => report.malformed(line_num, e.to_string())?,
+                }
+            } else if buf[0] == b'W' {
+                match Self::parse_walk_seq_to_signed_id_vec(&buf, &mut node_indexer) {
+                    Ok((sample, path_name, path, offset, circular)) => {
+                        let genome_name = if group_by {
+                            match Self::genome_of_path(&path_name, || sample, genome_rule) {
+                                Ok(genome_name) => genome_name,
+                                Err(reason) => {
+                                    report.malformed(line_num, reason)?;
+                                    buf.clear();
+                                    continue;
+                                }
+                            }
+                        } else {
+                            path_name.clone()
+                        };
+
+                        gfa_paths.push(GfaPath {
+                            genome_name,
+                            path_name,
+                            path,
+                            overlaps: None,
+                            offset,
+                            circular,
                         });
                     }
+                    Err(e) => report.malformed(line_num, e.to_string())?,
+                }
+            } else if buf[0] == b'S' {
+                match Self::parse_segment_length(&buf, &mut seg_indexer) {
+                    Ok((id, len)) => {
+                        if seg_lengths.len() <= id {
+                            seg_lengths.resize(id + 1, None);
+                        }
+                        seg_lengths[id] = len;
+                    }
+                    Err(e) => report.malformed(line_num, e.to_string())?,
                 }
-                num_paths += 1;
+            } else if buf[0] == b'L' {
+                match Self::parse_link(&buf, &mut seg_indexer) {
+                    Ok((from, to, overlap)) => {
+                        let from_rev = SignedId { id: from.id, plus: !from.plus };
+                        let to_rev = SignedId { id: to.id, plus: !to.plus };
+                        link_overlaps.insert((from, to), overlap);
+                        link_overlaps.insert((to_rev, from_rev), overlap);
+                    }
+                    Err(e) => report.malformed(line_num, e.to_string())?,
+                }
+            } else if buf[0] == b'O' {
+                has_groups = true;
             }
             buf.clear();
         }
+        report.summarize();
+
+        if gfa_paths.is_empty() {
+            return Err(MiceError::NoPaths {
+                file: filename.to_string(),
+                reason: if has_groups {
+                    "no P-lines or W-lines, O-lines are only read when the header declares VN:Z:2.0"
+                } else {
+                    "no P-lines or W-lines"
+                }
+                .to_string(),
+            }
+            .into());
+        }
+
+        Ok(Self::genome_bundle(gfa_paths, node_indexer, &seg_indexer, &seg_lengths, &link_overlaps))
+    }
+
+    // Groups the paths by genome, with coordinates if every segment in the paths has a length.
+    // Segments and links are indexed by seg_indexer, path elements by node_indexer.
+    pub(super) fn genome_bundle(
+        gfa_paths: Vec<GfaPath>,
+        node_indexer: NodeIndexer,
+        seg_indexer: &NodeIndexer,
+        seg_lengths: &[Option<usize>],
+        link_overlaps: &HashMap<(SignedId, SignedId), usize>,
+    ) -> (GenomeBundle, usize) {
+        let num_paths = gfa_paths.len();
+
+        let mut node_lengths: Vec<Option<usize>> = vec![None; node_indexer.next];
+        let mut node_to_seg: Vec<usize> = vec![UNINITIALIZED; node_indexer.next];
+        for (name, &id) in node_indexer.map.iter() {
+            if let Some(&seg) = seg_indexer.map.get(name) {
+                node_lengths[id] = seg_lengths.get(seg).copied().flatten();
+                node_to_seg[id] = seg;
+            }
+        }
+
+        // Coordinates are only available if every segment in the paths has a length
+        let coordinates: Option<Vec<(Vec<usize>, Vec<usize>)>> = gfa_paths
+            .iter()
+            .map(|p| Self::path_coordinates(p, &node_lengths, &node_to_seg, link_overlaps))
+            .collect();
+        if coordinates.is_none() {
+            eprintln!("Warning: missing segment lengths, element coordinates are not available");
+        }
+        let mut coordinates = coordinates.map(|c| c.into_iter());
+
+        let mut genomes: IndexMap<String, PathBundle> = IndexMap::default();
+        for gfa_path in gfa_paths {
+            let GfaPath { genome_name, path_name, path, circular, .. } = gfa_path;
+            let p = genomes.entry(genome_name).or_default();
+            if let Some((starts, ends)) = coordinates.as_mut().and_then(|c| c.next()) {
+                p.path_sizes.push(ends.last().copied().unwrap_or(0));
+                p.path_starts.push(starts);
+                p.path_ends.push(ends);
+            }
+            p.paths.push(path);
+            p.path_names.push(path_name);
+            p.path_circular.push(circular);
+        }
 
         let num_nodes = node_indexer.next;
         let node_indexer = Some(node_indexer);
@@ -116,6 +515,6 @@ impl Gfa {
             num_paths,
             node_indexer,
         };
-        Ok((genome_bundle, num_nodes))
+        (genome_bundle, num_nodes)
     }
 }
@@ -0,0 +1,283 @@
+use crate::io::*;
+use crate::io::gfa::{Gfa, GfaPath};
+use crate::synteny::SyntenyResult;
+use anyhow::{bail, Context, Result};
+use std::io::BufRead;
+use std::str;
+
+//GFA 2.0, genomes are the ordered groups (O-lines) of oriented segment references.
+//References to edges are skipped, references to other ordered groups are expanded,
+//and a group included in another group is not a path on its own.
+pub struct Gfa2;
+
+// An O-line, resolved once every group and edge name is known
+struct OrderedGroup {
+    name: String,
+    refs: Vec<(Vec<u8>, bool)>,
+    line: usize,
+    circular: bool,
+}
+
+impl GraphReader for Gfa2 {
+    fn read_paths(&self, config: &MiceConfig) -> Result<(GenomeBundle, usize)> {
+        let (genome_bundle, num_nodes) =
+            Self::parse_gfa2_paths(&config.input, config.group_by, config.genome_rule.as_ref(), config.strict)
+                .context("Error reading GFA2")?;
+
+        Ok((genome_bundle, num_nodes))
+    }
+
+    fn write_graph(&self, out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
+        Gfa.write_graph(out_dir, result)
+    }
+}
+
+impl Gfa2 {
+    // Header of a GFA starting with a VN:Z:2.x tag
+    pub fn is_gfa2(filename: &str) -> Result<bool> {
+        let reader = open_compressed_file(filename)?;
+        for line in reader.split(b'\n') {
+            let line = line.map_err(|source| MiceError::Read {
+                file: filename.to_string(),
+                source,
+            })?;
+            if line.first() != Some(&b'H') {
+                if line.is_empty() || line[0] == b'#' {
+                    continue;
+                }
+                // The header comes first
+                break;
+            }
+            for tag in Gfa::split_fields(&line).skip(1) {
+                if let Some(version) = tag.strip_prefix(b"VN:Z:") {
+                    return Ok(version.starts_with(b"2"));
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    #[inline]
+    fn parse_reference(reference: &[u8]) -> Result<(Vec<u8>, bool)> {
+        match reference.split_last() {
+            Some((b'+', name)) if !name.is_empty() => Ok((name.to_vec(), true)),
+            Some((b'-', name)) if !name.is_empty() => Ok((name.to_vec(), false)),
+            _ => bail!("invalid reference '{}'", String::from_utf8_lossy(reference)),
+        }
+    }
+
+    // Position of an E-line, with an optional $ marking the end of the segment
+    #[inline]
+    fn parse_position(pos: &[u8]) -> Result<usize> {
+        let pos = pos.strip_suffix(b"$").unwrap_or(pos);
+        Ok(str::from_utf8(pos)?.parse::<usize>()?)
+    }
+
+    // S-line (GFA 2):
+    // S  sid  slen  sequence
+    fn parse_segment_length(data: &[u8], seg_indexer: &mut NodeIndexer) -> Result<(usize, usize)> {
+        let mut f = Gfa::split_fields(data);
+        let _s = f.next();
+        let name = f.next().ok_or_else(|| anyhow::anyhow!("S-line without name"))?;
+        let len = f.next().ok_or_else(|| anyhow::anyhow!("S-line without length"))?;
+        let len = str::from_utf8(len)?.parse::<usize>()?;
+        Ok((seg_indexer.id_for(name), len))
+    }
+
+    // E-line (GFA 2):
+    // E  eid  sid1±  sid2±  beg1  end1  beg2  end2  alignment
+    // The overlap entering sid2 is end2 - beg2, the one entering sid1 (reverse) is end1 - beg1
+    fn parse_edge(
+        data: &[u8],
+        seg_indexer: &mut NodeIndexer,
+    ) -> Result<(Vec<u8>, SignedId, SignedId, usize, usize)> {
+        let mut f = Gfa::split_fields(data);
+        let _e = f.next();
+        let mut next_field = |name: &str| {
+            f.next()
+                .ok_or_else(|| anyhow::anyhow!("E-line without {} field", name))
+        };
+        let name = next_field("edge id")?.to_vec();
+        let (from, from_plus) = Self::parse_reference(next_field("sid1")?)?;
+        let (to, to_plus) = Self::parse_reference(next_field("sid2")?)?;
+        let beg1 = Self::parse_position(next_field("beg1")?)?;
+        let end1 = Self::parse_position(next_field("end1")?)?;
+        let beg2 = Self::parse_position(next_field("beg2")?)?;
+        let end2 = Self::parse_position(next_field("end2")?)?;
+
+        Ok((
+            name,
+            SignedId { id: seg_indexer.id_for(&from), plus: from_plus },
+            SignedId { id: seg_indexer.id_for(&to), plus: to_plus },
+            end2.saturating_sub(beg2),
+            end1.saturating_sub(beg1),
+        ))
+    }
+
+    // O-line (GFA 2):
+    // O  oid  references (space separated, e.g. s1+ s2- e1+)
+    fn parse_ordered_group(data: &[u8], line: usize) -> Result<OrderedGroup> {
+        let mut f = Gfa::split_fields(data);
+        let _o = f.next();
+        let name = f.next().ok_or_else(|| anyhow::anyhow!("O-line without group id"))?;
+        let refs = f.next().ok_or_else(|| anyhow::anyhow!("O-line without references"))?;
+        let refs = refs
+            .split(|&x| x == b' ')
+            .filter(|r| !r.is_empty())
+            .map(Self::parse_reference)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(OrderedGroup {
+            name: str::from_utf8(name)?.to_owned(),
+            refs,
+            line,
+            circular: Gfa::has_circular_tag(f),
+        })
+    }
+
+    // Segments of a group in path order, expanding nested groups
+    fn expand_group(
+        group: usize,
+        plus: bool,
+        groups: &[OrderedGroup],
+        group_ids: &HashMap<&[u8], usize>,
+        edges: &HashSet<Vec<u8>>,
+        visiting: &mut Vec<usize>,
+        segments: &mut Vec<(Vec<u8>, bool)>,
+    ) -> Result<()> {
+        if visiting.contains(&group) {
+            bail!("ordered group {} contains itself", groups[group].name);
+        }
+        visiting.push(group);
+        let refs = &groups[group].refs;
+        let ordered: Box<dyn Iterator<Item = &(Vec<u8>, bool)>> = if plus {
+            Box::new(refs.iter())
+        } else {
+            Box::new(refs.iter().rev())
+        };
+        for (name, ref_plus) in ordered {
+            let ref_plus = *ref_plus == plus;
+            if let Some(&sub) = group_ids.get(name.as_slice()) {
+                Self::expand_group(sub, ref_plus, groups, group_ids, edges, visiting, segments)?;
+            } else if !edges.contains(name) {
+                segments.push((name.clone(), ref_plus));
+            }
+        }
+        visiting.pop();
+        Ok(())
+    }
+
+    pub fn parse_gfa2_paths(
+        filename: &str,
+        group_by: bool,
+        genome_rule: Option<&GenomeRule>,
+        strict: bool,
+    ) -> Result<(GenomeBundle, usize)> {
+        Gfa::check_genome_rule(genome_rule)?;
+        let mut reader = bufreader_from_compressed_file(filename)?;
+        let mut report = ParseReport::new(filename, strict);
+
+        let mut groups: Vec<OrderedGroup> = Vec::new();
+        let mut edges: HashSet<Vec<u8>> = HashSet::default();
+        let mut seg_lengths: Vec<Option<usize>> = Vec::new();
+        let mut link_overlaps: HashMap<(SignedId, SignedId), usize> = HashMap::default();
+        let mut seg_indexer = NodeIndexer::new();
+
+        let mut buf = vec![];
+        let mut line_num = 0usize;
+        while reader.read_until(b'\n', &mut buf).map_err(|e| report.read_error(e))? > 0 {
+            line_num += 1;
+            if buf[0] == b'O' {
+                match Self::parse_ordered_group(&buf, line_num) {
+                    Ok(group) => groups.push(group),
+                    Err(e) => report.malformed(line_num, e.to_string())?,
+                }
+            } else if buf[0] == b'S' {
+                match Self::parse_segment_length(&buf, &mut seg_indexer) {
+                    Ok((id, len)) => {
+                        if seg_lengths.len() <= id {
+                            seg_lengths.resize(id + 1, None);
+                        }
+                        seg_lengths[id] = Some(len);
+                    }
+                    Err(e) => report.malformed(line_num, e.to_string())?,
+                }
+            } else if buf[0] == b'E' {
+                match Self::parse_edge(&buf, &mut seg_indexer) {
+                    Ok((name, from, to, overlap, rev_overlap)) => {
+                        let from_rev = SignedId { id: from.id, plus: !from.plus };
+                        let to_rev = SignedId { id: to.id, plus: !to.plus };
+                        link_overlaps.insert((from, to), overlap);
+                        link_overlaps.insert((to_rev, from_rev), rev_overlap);
+                        if name != b"*" {
+                            edges.insert(name);
+                        }
+                    }
+                    Err(e) => report.malformed(line_num, e.to_string())?,
+                }
+            }
+            buf.clear();
+        }
+
+        let group_ids: HashMap<&[u8], usize> = groups
+            .iter()
+            .enumerate()
+            .map(|(i, group)| (group.name.as_bytes(), i))
+            .collect();
+        let nested: HashSet<usize> = groups
+            .iter()
+            .flat_map(|group| group.refs.iter())
+            .filter_map(|(name, _)| group_ids.get(name.as_slice()).copied())
+            .collect();
+
+        let mut gfa_paths: Vec<GfaPath> = Vec::new();
+        let mut node_indexer = NodeIndexer::new();
+        for (i, group) in groups.iter().enumerate() {
+            if nested.contains(&i) {
+                continue;
+            }
+            let mut segments = Vec::new();
+            let mut visiting = Vec::new();
+            if let Err(e) = Self::expand_group(i, true, &groups, &group_ids, &edges, &mut visiting, &mut segments) {
+                report.malformed(group.line, e.to_string())?;
+                continue;
+            }
+            let path: Path = segments
+                .iter()
+                .map(|(name, plus)| SignedId { id: node_indexer.id_for(name), plus: *plus })
+                .collect();
+
+            let genome_name = if group_by {
+                let default = || Gfa::path_name_to_genome_string(group.name.clone());
+                match Gfa::genome_of_path(&group.name, default, genome_rule) {
+                    Ok(genome_name) => genome_name,
+                    Err(reason) => {
+                        report.malformed(group.line, reason)?;
+                        continue;
+                    }
+                }
+            } else {
+                group.name.clone()
+            };
+            gfa_paths.push(GfaPath {
+                genome_name,
+                path_name: group.name.clone(),
+                path,
+                overlaps: None,
+                offset: 0,
+                circular: group.circular,
+            });
+        }
+        report.summarize();
+
+        if gfa_paths.is_empty() {
+            return Err(MiceError::NoPaths {
+                file: filename.to_string(),
+                reason: "no O-lines".to_string(),
+            }
+            .into());
+        }
+
+        Ok(Gfa::genome_bundle(gfa_paths, node_indexer, &seg_indexer, &seg_lengths, &link_overlaps))
+    }
+}
@@ -1,244 +1,226 @@
 use crate::io::*;
-use anyhow::Result;
-use std::collections::hash_map;
+use crate::synteny::{BlockClass, BlockInterval, IntervalKind, SyntenyResult};
+use anyhow::{Context, Result};
 use std::io::BufRead;
 use std::str;
 
 //The Gff assumed here are from SibeliaZ and Cactus with a ID as attributes and a 1-based index
+//The element is named by the ID attribute by default, or by the id attribute of the config
+//(e.g. Name, orthogroup, cluster). A positive integer ID keeps its 1-based index, any other string
+//(and any value of another attribute, e.g. a sparse cluster number) is a valid element name
+//numbered after the largest index.
 //example:
 //Genome1.Chr2	SibeliaZ	SO:0000856	524938	525114	.	+	.	ID=1
 //Genome1.Chr1	SibeliaZ	SO:0000856	521833	522009	.	+	.	ID=2
 //Genome2.Chr4	SibeliaZ	SO:0000856	536437	536613	.	+	.	ID=2
+//A `region` feature describes a sequence (its size, and `Is_circular=true` for a circular one)
+//instead of being an element.
+//The genome of a feature is its `genome` attribute by default, or is found by the genome rule
+//of the config (e.g. the seqid before the first '.' here). Without a rule, a feature missing the
+//`genome` attribute falls back to its seqid before the first '#' (PanSN), or else before the first '.'.
 pub struct Gff;
 
 impl GraphReader for Gff {
-    fn read_paths(&self, gfa_input: &str, group_by: bool) -> Result<(GenomeBundle, usize)> {
-        let (genome_bundle, num_nodes) = Self::parse_gff_paths(gfa_input, group_by)
-            .map_err(|e| anyhow::anyhow!("Error reading GFF: {}", e))?;
+    fn read_paths(&self, config: &MiceConfig) -> Result<(GenomeBundle, usize)> {
+        let (genome_bundle, num_nodes) =
+            Self::parse_gff_paths(
+                &config.input,
+                config.group_by,
+                config.genome_rule.as_ref(),
+                &config.id_attribute,
+                config.strict,
+            )
+            .context("Error reading GFF")?;
 
         Ok((genome_bundle, num_nodes))
     }
 
-    fn write_graph(
-        &self,
-        out_dir: &path::Path,
-        genomes: &HashMap<String, PathBundle>,
-        node_to_part: &[usize],
-    ) -> Result<()> {
+    fn write_graph(&self, out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
         let output = out_dir.join("output.gff");
         std::fs::remove_file(&output).ok();
         let file = File::create(output)?;
-        let mut writer = BufWriter::new(file);
+        Self::write_gff(&mut BufWriter::new(file), result)
+    }
+}
+
+struct GffRow {
+    start: usize,
+    end: usize,
+    strand: bool,
+    id: GffId,
+}
+
+// Element of a row, numbered once every row is read
+#[derive(Clone, Copy)]
+enum GffId {
+    // 0-based index of a positive integer ID
+    Index(usize),
+    // Id of a textual name in the node indexer
+    Name(usize),
+}
+
+struct BoundedPath {
+    pub path: Vec<(GffId, bool)>,
+    pub path_starts: Vec<usize>,
+    pub path_ends: Vec<usize>,
+    pub genome_name: Option<String>,
+}
 
+impl Gff {
+    pub fn write_gff(writer: &mut impl Write, result: &SyntenyResult) -> Result<()> {
         writeln!(writer, "##gff-version 3")?;
 
         //Header
-        for genome in genomes.values() {
-            for (path_size, path_name) in genome.path_sizes.iter().zip(genome.path_names.iter()) {
-                writeln!(writer, "##sequence-region {path_name} 1 {path_size}")?;
+        for genome in result.genomes.iter() {
+            for path in genome.paths.iter() {
+                if let Some(path_size) = path.size {
+                    writeln!(writer, "##sequence-region {} 1 {path_size}", path.name)?;
+                }
+            }
+        }
+        //Circular sequences
+        for genome in result.genomes.iter() {
+            for path in genome.paths.iter().filter(|p| p.circular) {
+                if let Some(path_size) = path.size {
+                    writeln!(writer, "{}\tmice\tregion\t1\t{path_size}\t.\t+\t.\tIs_circular=true", path.name)?;
+                }
             }
         }
         //Gff
-        for (genome_name, genome) in genomes.iter() {
-            for (z, path) in genome.paths.iter().enumerate() {
-                let path_name = &genome.path_names[z];
-                let path_starts = &genome.path_starts[z];
-                let path_ends = &genome.path_ends[z];
-                let mut i = 0usize;
-                while i < path.len() {
-                    while i < path.len() && node_to_part[path[i].id] == FILTERED {
-                        i += 1;
-                    }
-                    if i == path.len() {
-                        break;
-                    }
-
-                    let part_i = node_to_part[path[i].id]; //First non-FILTERED
-                    let mut j = i;
-                    while j + 1 < path.len()
-                        && (part_i == node_to_part[path[j + 1].id]
-                            || node_to_part[path[j + 1].id] == FILTERED)
-                    {
-                        j += 1;
-                    }
-
-                    let end = j;
-                    while node_to_part[path[j].id] == FILTERED {
-                        j -= 1;
-                    }
-                    // This is true for the clean mode, but not for the dirty
-                    // This is the situation:
-                    //               ...i            j      ...
-                    // node_to_part: ...pi pi F F pi pi F px...  (pi = part_i, px!=pi)
-
-                    //There are two cases:
-                    // node_to_part: ...pi pi F F pi pi...  (pi = part_i)
-                    // core:         ...0  1  0 0 0  0 ...
-                    // or
-                    // core:         ...1  1  0 0 1  1 ...
-
-                    //Count number of core elements (either 1 or many)
-                    let mut count_core = 0usize;
-                    let mut last_el_core = SignedId {
-                        id: UNINITIALIZED,
-                        plus: false,
-                    };
-                    for l in i..=j {
-                        if node_to_part[path[l].id] == path[l].id {
-                            count_core += 1;
-                            last_el_core = path[l];
-                        }
-                    }
-
-                    match count_core {
-                        1 => {
-                            let part = node_to_part[last_el_core.id];
-                            let strand = if last_el_core.plus { '+' } else { '-' };
-                            let first_start = path_starts[i];
-                            let last_end = path_ends[j];
-                            writeln!(writer, "{path_name}\tmice\tSO:0000856\t{first_start}\t{last_end}\t.\t{strand}\t.\tID={};genome={genome_name}", part+1)?;
-
-                            // Obscured filtered ranges
-                            let mut l = i + 1;
-                            while l <= j {
-                                if node_to_part[path[l].id] == FILTERED {
-                                    let start_filter_pos = l;
-                                    let mut m = l + 1;
-                                    while m <= j && node_to_part[path[m].id] == FILTERED {
-                                        m += 1;
-                                    }
-                                    let end_filter_pos = m - 1;
-                                    let left_start_filtered = path_starts[start_filter_pos];
-                                    let right_end_filtered = path_ends[end_filter_pos];
-                                    let left_end_part = path_ends[start_filter_pos - 1];
-                                    let right_start_part = path_starts[end_filter_pos + 1];
-                                    if right_start_part > left_end_part + 1 {
-                                        let start =
-                                            usize::max(left_start_filtered, left_end_part + 1);
-                                        let end =
-                                            usize::min(right_end_filtered, right_start_part - 1);
-                                        writeln!(writer, "{path_name}\tmice\tfiltered\t{start}\t{end}\t.\t{strand}\t.\tgenome={genome_name}")?;
-                                    }
-                                    l = m;
-                                }
-                                l += 1;
-                            }
-
-                            // Obscured Ns ranges
-                            for l in i..j {
-                                let left_end = path_ends[l];
-                                let right_start = path_starts[l + 1];
-                                if right_start > left_end + 1 {
-                                    //No overlap
-                                    let left_end = left_end + 1;
-                                    let right_start = right_start - 1;
-                                    writeln!(writer, "{path_name}\tmice\tNs\t{left_end}\t{right_start}\t.\t{strand}\t.\tgenome={genome_name}")?;
-                                }
-                            }
-                        }
-                        n if n > 1 => {
-                            let mut l = i;
-                            while l <= j {
-                                let start = path_starts[l];
-                                while l <= j && node_to_part[path[l].id] != path[l].id {
-                                    l += 1;
-                                }
-                                if l <= j {
-                                    let part = node_to_part[path[l].id];
-                                    let strand = if path[l].plus { '+' } else { '-' };
-                                    while l < j && node_to_part[path[l+1].id] != path[l+1].id {
-                                        l += 1;
-                                    }
-                                    let end = path_ends[l];
-                                    writeln!(writer, "{path_name}\tmice\tSO:0000856\t{start}\t{end}\t.\t{strand}\t.\tID={};genome={genome_name}", part+1)?;
-                                    l += 1;
-                                }
-                            }
-                        }
-                        _ => {
-                            eprintln!("Error: no core found in a substring in {genome_name}:");
-                            let part = node_to_part[path[i].id];
-                            for x in path[i..=j].iter() {
-                                if part != node_to_part[x.id] {
-                                    eprintln!("the parts are not correct");
-                                }
-                            }
-                            eprintln!("part: {}", part+1);
-                            for x in path[i..=j].iter() {
-                                eprint!("{}{} ", x.id+1, if x.plus {'+'} else {'-'});
-                            }
-                            eprintln!();
-                            return Ok(());
+        let classes: HashMap<usize, (BlockClass, usize)> =
+            result.blocks.iter().map(|b| (b.id, (b.class, b.num_genomes))).collect();
+        for genome in result.genomes.iter() {
+            let genome_name = &genome.name;
+            for path in genome.paths.iter() {
+                let path_name = &path.name;
+                for interval in path.intervals.iter().flatten() {
+                    let BlockInterval { kind, start, end, plus } = *interval;
+                    let strand = if plus { '+' } else { '-' };
+                    match kind {
+                        IntervalKind::Block(id) => {
+                            let (class, num_genomes) = classes[&id];
+                            writeln!(writer, "{path_name}\tmice\tSO:0000856\t{start}\t{end}\t.\t{strand}\t.\tID={id};genome={genome_name};class={class};num_genomes={num_genomes}")?
                         }
+                        IntervalKind::Filtered => writeln!(writer, "{path_name}\tmice\tfiltered\t{start}\t{end}\t.\t{strand}\t.\tgenome={genome_name}")?,
+                        IntervalKind::Ns => writeln!(writer, "{path_name}\tmice\tNs\t{start}\t{end}\t.\t{strand}\t.\tgenome={genome_name}")?,
+                        IntervalKind::Insertion => writeln!(writer, "{path_name}\tmice\tinsertion\t{start}\t{end}\t.\t{strand}\t.\tgenome={genome_name}")?,
                     }
-                    i = end + 1;
                 }
             }
         }
 
         Ok(())
     }
-}
 
-struct GffRow {
-    start: usize,
-    end: usize,
-    strand: bool,
-    id: usize,
-}
-
-struct BoundedPath {
-    pub path: Path,
-    pub path_starts: Vec<usize>,
-    pub path_ends: Vec<usize>,
-    pub genome_name: Option<String>,
-}
+    #[inline]
+    fn utf8_field<'a>(field: &'a [u8], name: &str) -> Result<&'a str, String> {
+        str::from_utf8(field).map_err(|_| format!("{name} is not valid UTF-8"))
+    }
 
-impl Gff {
-    fn extract_gff_info_from_row(line: &[u8]) -> Option<(String, String, GffRow)> {
+    // The genome is only required to group the paths by genome (genome_rule is None otherwise)
+    fn extract_gff_info_from_row(
+        line: &[u8],
+        genome_rule: Option<&GenomeRule>,
+        seqid_fallback: bool,
+        id_attribute: &str,
+        node_indexer: &mut NodeIndexer,
+    ) -> Result<(String, Option<String>, GffRow), String> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
         let mut f = line.splitn(9, |&b| b == b'\t');
+        let mut next_field = |name: &str| f.next().ok_or_else(|| format!("missing {name} column"));
+        let utf8 = Self::utf8_field;
+        let position = |field: &[u8], name: &str| {
+            utf8(field, name)?
+                .parse::<usize>()
+                .map_err(|_| format!("invalid {name} position '{}'", String::from_utf8_lossy(field)))
+        };
 
-        let seqname = str::from_utf8(f.next()?).ok()?.to_owned();
-        let _source = f.next()?;
-        let _feature = f.next()?;
-        let start = str::from_utf8(f.next()?).ok()?.parse::<usize>().ok()?;
-        let end = str::from_utf8(f.next()?).ok()?.parse::<usize>().ok()?;
-        let _score = f.next()?;
-        let strand = f.next()? == b"+";
-        let _frame = f.next()?;
-        let attributes = str::from_utf8(f.next()?).ok()?;
-
+        let seqname = utf8(next_field("seqid")?, "seqid")?.to_owned();
+        let _source = next_field("source")?;
+        let _feature = next_field("type")?;
+        let start = position(next_field("start")?, "start")?;
+        let end = position(next_field("end")?, "end")?;
+        let _score = next_field("score")?;
+        let strand = next_field("strand")? == b"+";
+        let _frame = next_field("phase")?;
+        let attributes = utf8(next_field("attributes")?, "attributes")?;
+
+        let genome_attribute = match genome_rule {
+            Some(GenomeRule::Attribute(attribute)) => Some(attribute.as_str()),
+            _ => None,
+        };
         let mut id = None;
         let mut genome_name = None;
 
         for key_val in attributes.split(';') {
             let key_val = key_val.trim();
             if let Some((key, val)) = key_val.split_once('=') {
-                if key.eq_ignore_ascii_case("id") {
-                    if let Ok(parsed) = val.trim().parse::<usize>() {
-                        id = Some(parsed);
+                if key.eq_ignore_ascii_case(id_attribute) {
+                    let val = val.trim();
+                    if val.is_empty() {
+                        return Err(format!("empty {id_attribute} attribute"));
                     }
-                } else if key.eq_ignore_ascii_case("genome") {
+                    id = Some(val);
+                } else if genome_attribute.is_some_and(|attribute| key.eq_ignore_ascii_case(attribute)) {
                     genome_name = Some(String::from(val));
                 }
             }
         }
 
-        let id = id? - 1; // 1-based index (based on SibeliaZ, Cactus and gfa2gff)
-        let genome_name = genome_name?;
+        let id = id.ok_or_else(|| format!("missing {id_attribute} attribute"))?;
+        match genome_rule {
+            Some(GenomeRule::Attribute(_)) if genome_name.is_none() && seqid_fallback => {
+                genome_name = Some(Self::genome_from_seqid(&seqname));
+            }
+            Some(GenomeRule::Attribute(attribute)) if genome_name.is_none() => {
+                return Err(format!("missing {attribute} attribute"));
+            }
+            Some(GenomeRule::Attribute(_)) | None => {}
+            Some(rule) => {
+                genome_name = Some(rule.genome_from_name(&seqname).ok_or_else(|| format!("no genome for seqid {seqname}"))?);
+            }
+        }
 
-        Some((
+        Ok((
             seqname,
             genome_name,
             GffRow {
                 start,
                 end,
                 strand,
-                id,
+                id: match id.parse::<usize>() {
+                    Ok(index) if index > 0 && id_attribute.eq_ignore_ascii_case("id") => {
+                        GffId::Index(index - 1) // 1-based index (based on SibeliaZ, Cactus and gfa2gff)
+                    }
+                    _ => GffId::Name(node_indexer.id_for(id.as_bytes())),
+                },
             },
         ))
     }
 
+    // e.g. HG002#1#chr1 -> HG002, Genome1.Chr2 -> Genome1
+    fn genome_from_seqid(seqid: &str) -> String {
+        let delimiter = if seqid.contains('#') { '#' } else { '.' };
+        seqid.split_once(delimiter).map_or(seqid, |(genome, _)| genome).to_string()
+    }
+
+    // region feature (GFF3): the whole sequence, e.g. from NCBI
+    // NC_000913.3  RefSeq  region  1  4641652  .  +  .  ID=NC_000913.3:1..4641652;Is_circular=true
+    fn parse_region_row(line: &[u8]) -> Option<(String, usize, bool)> {
+        let line = str::from_utf8(line).ok()?.trim_end();
+        let f: Vec<&str> = line.splitn(9, '\t').collect();
+        if f.len() < 9 || f[2] != "region" {
+            return None;
+        }
+        let end = f[4].parse::<usize>().ok()?;
+        let circular = f[8].split(';').any(|key_val| match key_val.trim().split_once('=') {
+            Some((key, val)) => key.eq_ignore_ascii_case("is_circular") && val.eq_ignore_ascii_case("true"),
+            None => false,
+        });
+        Some((f[0].to_string(), end, circular))
+    }
+
     fn parse_gff_header(line: &[u8]) -> Option<(String, usize, usize)> {
         let mut f = line.splitn(4, |&b| b == b' ');
         let _ = str::from_utf8(f.next()?).ok()?.to_owned();
@@ -253,66 +235,85 @@ impl Gff {
         Some((seqname, start, end))
     }
 
-    pub fn parse_gff_paths(filename: &str, group_by: bool) -> Result<(GenomeBundle, usize)> {
-        let mut bounded_paths: HashMap<String, BoundedPath> = HashMap::default();
+    pub fn parse_gff_paths(
+        filename: &str,
+        group_by: bool,
+        genome_rule: Option<&GenomeRule>,
+        id_attribute: &str,
+        strict: bool,
+    ) -> Result<(GenomeBundle, usize)> {
+        let default_rule = GenomeRule::Attribute("genome".to_string());
+        let seqid_fallback = genome_rule.is_none();
+        let genome_rule = group_by.then(|| genome_rule.unwrap_or(&default_rule));
+        let mut bounded_paths: IndexMap<String, BoundedPath> = IndexMap::default();
         let mut header: HashMap<String, usize> = HashMap::default();
-        let mut num_nodes = 0usize;
+        let mut circular_paths: HashSet<String> = HashSet::default();
+        let mut node_indexer = NodeIndexer::new();
+        let mut num_indexed = 0usize;
 
-        let mut reader = bufreader_from_compressed_file(filename);
+        let mut reader = bufreader_from_compressed_file(filename)?;
+        let mut report = ParseReport::new(filename, strict);
 
         // Parsing gff
         let mut buf = vec![];
-        while reader.read_until(b'\n', &mut buf).unwrap_or(0) > 0 {
-            if buf[0] != b'#' {
-                if let Some((path_name, genome_name, row)) = Self::extract_gff_info_from_row(&buf) {
-                    num_nodes = usize::max(row.id, num_nodes);
-
-                    let genome_name = if group_by { Some(genome_name) } else { None };
-
-                    let el = SignedId {
-                        id: row.id,
-                        plus: row.strand,
-                    };
-                    let entry = bounded_paths.entry(path_name);
-                    match entry {
-                        hash_map::Entry::Occupied(mut p) => {
-                            p.get_mut().path.push(el);
-                            p.get_mut().path_starts.push(row.start);
-                            p.get_mut().path_ends.push(row.end);
+        let mut line_num = 0usize;
+        while reader.read_until(b'\n', &mut buf).map_err(|e| report.read_error(e))? > 0 {
+            line_num += 1;
+            if buf.trim_ascii().is_empty() {
+                // Empty line
+            } else if let Some((path_name, end, circular)) = Self::parse_region_row(&buf) {
+                if circular {
+                    circular_paths.insert(path_name.clone());
+                }
+                header.entry(path_name).or_insert(end);
+            } else if buf[0] != b'#' {
+                match Self::extract_gff_info_from_row(&buf, genome_rule, seqid_fallback, id_attribute, &mut node_indexer) {
+                    Ok((path_name, genome_name, row)) => {
+                        if let GffId::Index(index) = row.id {
+                            num_indexed = num_indexed.max(index + 1);
                         }
-                        hash_map::Entry::Vacant(p) => {
-                            p.insert(BoundedPath {
-                                path: vec![el],
-                                path_starts: vec![row.start],
-                                path_ends: vec![row.end],
-                                genome_name,
-                            });
+                        let el = (row.id, row.strand);
+                        let entry = bounded_paths.entry(path_name);
+                        match entry {
+                            IndexMapEntry::Occupied(mut p) => {
+                                p.get_mut().path.push(el);
+                                p.get_mut().path_starts.push(row.start);
+                                p.get_mut().path_ends.push(row.end);
+                            }
+                            IndexMapEntry::Vacant(p) => {
+                                p.insert(BoundedPath {
+                                    path: vec![el],
+                                    path_starts: vec![row.start],
+                                    path_ends: vec![row.end],
+                                    genome_name,
+                                });
+                            }
                         }
                     }
-                } else {
-                    eprintln!(
-                        "Error: unable to read line: {}",
-                        str::from_utf8(&buf).unwrap()
-                    );
+                    Err(reason) => report.malformed(line_num, reason)?,
                 }
-            } else if !buf.starts_with(b"##gff-version") {
+            } else if buf.starts_with(b"##sequence-region") {
                 if let Some((path_name, _start, end)) = Self::parse_gff_header(&buf) {
                     header.insert(path_name, end);
                 } else {
-                    eprintln!(
-                        "Error: unable to read header: {}",
-                        str::from_utf8(&buf).unwrap()
-                    );
+                    report.malformed(line_num, "invalid ##sequence-region header")?;
                 }
             }
             buf.clear();
         }
-        num_nodes += 1;
-
         let num_paths = bounded_paths.len();
 
+        // Names are numbered after the indices
+        let element_id = |(id, plus): (GffId, bool)| SignedId {
+            id: match id {
+                GffId::Index(index) => index,
+                GffId::Name(name) => num_indexed + name,
+            },
+            plus,
+        };
+
         //Populate genomes and sort each bounded_path based on bounded_path.path_starts
-        let mut genomes: HashMap<String, PathBundle> = HashMap::default();
+        let mut genomes: IndexMap<String, PathBundle> = IndexMap::default();
         for (path_name, bounded_path) in bounded_paths {
             let path_len = bounded_path.path.len();
 
@@ -323,39 +324,68 @@ impl Gff {
             let mut path_starts_new = Vec::with_capacity(path_len);
             let mut path_ends_new = Vec::with_capacity(path_len);
             for &i in idx.iter() {
-                path_new.push(bounded_path.path[i]);
+                path_new.push(element_id(bounded_path.path[i]));
                 path_starts_new.push(bounded_path.path_starts[i]);
                 path_ends_new.push(bounded_path.path_ends[i]);
             }
 
+            // The size of a path missing from the header falls back to its last position
+            let mut path_size = None;
+            if !header.is_empty() {
+                path_size = match header.get(&path_name) {
+                    Some(&size) => Some(size),
+                    None => {
+                        report.skip(MiceError::MissingSequenceRegion {
+                            file: report.file().to_string(),
+                            path: path_name.clone(),
+                        })?;
+                        path_ends_new.iter().max().copied()
+                    }
+                };
+            }
+
+            let circular = circular_paths.contains(&path_name);
             let entry = genomes.entry(bounded_path.genome_name.unwrap_or(path_name.clone()));
             match entry {
-                hash_map::Entry::Occupied(mut p) => {
-                    if !header.is_empty() {
-                        p.get_mut().path_sizes.push(header[&path_name]);
-                    }
+                IndexMapEntry::Occupied(mut p) => {
+                    p.get_mut().path_circular.push(circular);
+                    p.get_mut().path_sizes.extend(path_size);
                     p.get_mut().paths.push(path_new);
                     p.get_mut().path_names.push(path_name);
                     p.get_mut().path_starts.push(path_starts_new);
                     p.get_mut().path_ends.push(path_ends_new);
                 }
-                hash_map::Entry::Vacant(p) => {
-                    let mut path_sizes = Vec::new();
-                    if !header.is_empty() {
-                        path_sizes.push(header[&path_name]);
-                    }
+                IndexMapEntry::Vacant(p) => {
                     p.insert(PathBundle {
                         paths: vec![path_new],
                         path_names: vec![path_name],
                         path_starts: vec![path_starts_new],
                         path_ends: vec![path_ends_new],
-                        path_sizes,
+                        path_sizes: path_size.into_iter().collect(),
+                        path_circular: vec![circular],
+                        ..PathBundle::default()
                     });
                 }
             }
         }
+        report.summarize();
+        if genomes.is_empty() {
+            return Err(MiceError::NoPaths {
+                file: filename.to_string(),
+                reason: format!("no feature with an {id_attribute} attribute"),
+            }
+            .into());
+        }
 
-        let node_indexer = None;
+        let num_nodes = num_indexed + node_indexer.next;
+        // Without names the elements keep the default 1-based index
+        let node_indexer = (node_indexer.next > 0).then(|| {
+            let mut element_names = NodeIndexer::new();
+            element_names.map = (0..num_indexed).map(|id| ((id + 1).to_string().into_bytes(), id)).collect();
+            element_names.map.extend(node_indexer.map.into_iter().map(|(name, id)| (name, num_indexed + id)));
+            element_names.next = num_nodes;
+            element_names
+        });
         let genome_bundle = GenomeBundle {
             genomes,
             num_paths,
@@ -0,0 +1,150 @@
+use crate::config::GenomeFile;
+use crate::io::*;
+use anyhow::{bail, Context, Result};
+
+//Several input files merged into one run, each file holding one genome (or part of one).
+//The files are given in the config, or listed in a manifest with their genome names:
+//genome1.gff	Genome1
+//dir/genome2.gfa.gz	Genome2
+//A line without genome name is named after the file, relative paths are looked up
+//from the working directory, then from the directory of the manifest.
+//Elements with the same name in two files are the same element.
+pub struct Manifest {
+    genome_files: Vec<GenomeFile>,
+    // Reader of the files when they share a format, else the block graph, which writes the outputs
+    writer: Box<dyn GraphReader>,
+}
+
+impl GraphReader for Manifest {
+    fn read_paths(&self, config: &MiceConfig) -> Result<(GenomeBundle, usize)> {
+        Self::merge_genome_files(config, &self.genome_files)
+    }
+
+    fn write_graph(&self, out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
+        self.writer.write_graph(out_dir, result)
+    }
+}
+
+impl Manifest {
+    pub fn new(config: &MiceConfig) -> Result<Self> {
+        let genome_files = if config.genome_files.is_empty() {
+            Self::read_manifest(&config.input)
+                .with_context(|| format!("Error reading manifest {}", config.input))?
+        } else {
+            config.genome_files.clone()
+        };
+        let formats = genome_files
+            .iter()
+            .map(|genome_file| input_format(&Self::file_config(config, genome_file)))
+            .collect::<Result<Vec<_>>>()?;
+        let writer: Box<dyn GraphReader> = if formats.iter().all(|&format| format == formats[0]) {
+            find_graph_type(&Self::file_config(config, &genome_files[0]))?
+        } else {
+            // Mixed formats: the block graph (and block coordinates) fit every input
+            Box::new(gfa::Gfa)
+        };
+        Ok(Self { genome_files, writer })
+    }
+
+    // Settings to read one file on its own
+    fn file_config(config: &MiceConfig, genome_file: &GenomeFile) -> MiceConfig {
+        let mut file_config = config.clone();
+        file_config.input = genome_file.file.clone();
+        file_config.genome_files = Vec::new();
+        file_config.group_by = false;
+        file_config
+    }
+
+    fn read_manifest(filename: &str) -> Result<Vec<GenomeFile>> {
+        if filename == "-" {
+            bail!("a manifest cannot be read from stdin");
+        }
+        let dir = path::Path::new(filename).parent().unwrap_or(path::Path::new(""));
+        let mut genome_files = Vec::new();
+        let reader = bufreader_from_compressed_file(filename)?;
+        for line in reader.lines() {
+            let line = line.map_err(|source| MiceError::Read {
+                file: filename.to_string(),
+                source,
+            })?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (file, genome) = match line.split_once('\t') {
+                Some((file, genome)) => (file.trim(), Some(genome.trim().to_string())),
+                None => (line, None),
+            };
+            let path = path::Path::new(file);
+            let file = if path.exists() || path.is_absolute() {
+                file.to_string()
+            } else {
+                dir.join(path).to_string_lossy().into_owned()
+            };
+            genome_files.push(GenomeFile { file, genome });
+        }
+        if genome_files.is_empty() {
+            bail!("{filename} lists no input file");
+        }
+        Ok(genome_files)
+    }
+
+    // Reads every file on its own and renames the elements by their name in a shared indexer
+    fn merge_genome_files(config: &MiceConfig, genome_files: &[GenomeFile]) -> Result<(GenomeBundle, usize)> {
+        let mut genomes: IndexMap<String, PathBundle> = IndexMap::default();
+        let mut node_indexer = NodeIndexer::new();
+        let mut num_paths = 0usize;
+
+        for genome_file in genome_files.iter() {
+            let file_config = Self::file_config(config, genome_file);
+            let reader = find_graph_type(&file_config)?;
+            if !reader.global_element_names() {
+                bail!(
+                    "{}: elements of this format cannot be merged across files",
+                    genome_file.file
+                );
+            }
+            let (bundle, num_nodes) = reader.read_paths(&file_config)?;
+
+            let names: Vec<Vec<u8>> = match bundle.node_indexer {
+                Some(file_indexer) => {
+                    let mut names = vec![Vec::new(); num_nodes];
+                    for (name, id) in file_indexer.map {
+                        names[id] = name;
+                    }
+                    names
+                }
+                // default 1-based index
+                None => (1..=num_nodes).map(|x| x.to_string().into_bytes()).collect(),
+            };
+            let ids: Vec<usize> = names.iter().map(|name| node_indexer.id_for(name)).collect();
+
+            let genome_name = genome_file
+                .genome
+                .clone()
+                .unwrap_or_else(|| genome_name_from_file(&genome_file.file));
+            num_paths += bundle.num_paths;
+            for (key, mut genome) in bundle.genomes {
+                for path in genome.paths.iter_mut() {
+                    for el in path.iter_mut() {
+                        el.id = ids[el.id];
+                    }
+                }
+                let key = if config.group_by {
+                    genome_name.clone()
+                } else {
+                    format!("{genome_name}#{key}")
+                };
+                genomes.entry(key).or_default().append(genome);
+            }
+        }
+
+        let num_nodes = node_indexer.next;
+        let genome_bundle = GenomeBundle {
+            genomes,
+            num_paths,
+            node_indexer: Some(node_indexer),
+        };
+        Ok((genome_bundle, num_nodes))
+    }
+}
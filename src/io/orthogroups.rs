@@ -0,0 +1,291 @@
+use crate::io::*;
+use anyhow::{bail, Context, Result};
+use std::io::BufRead;
+use std::str;
+
+//Genes as elements: the input is an orthogroup table (OrthoFinder Orthogroups.tsv) and each
+//genome comes with its GFF3 annotation. Every gene is replaced by its orthogroup, on the strand
+//and at the coordinates of the gene in the annotation.
+//Orthogroups.tsv:
+//Orthogroup	GenomeA	GenomeB
+//OG0000000	geneA1, geneA2	geneB1
+pub struct Orthogroups;
+
+// Attributes whose values can name a gene in the orthogroup table
+const GENE_ATTRIBUTES: [&str; 6] = ["ID", "Name", "protein_id", "locus_tag", "gene", "transcript_id"];
+
+// A feature of the annotation, genes are the features without parent
+struct Feature {
+    seqid: String,
+    start: usize,
+    end: usize,
+    plus: bool,
+    id: Option<String>,
+    parent: Option<String>,
+}
+
+impl GraphReader for Orthogroups {
+    fn read_paths(&self, config: &MiceConfig) -> Result<(GenomeBundle, usize)> {
+        let (genome_bundle, num_nodes) = Self::parse_orthogroup_paths(
+            &config.input,
+            &config.annotations,
+            config.group_by,
+            config.strict,
+        )
+        .context("Error reading orthogroups")?;
+
+        Ok((genome_bundle, num_nodes))
+    }
+
+    // Orthogroup names are specific to one table
+    fn global_element_names(&self) -> bool {
+        false
+    }
+
+    fn write_graph(&self, out_dir: &path::Path, result: &SyntenyResult) -> Result<()> {
+        super::gff::Gff.write_graph(out_dir, result)
+    }
+}
+
+impl Orthogroups {
+    // An annotation is given as FILE, named after the file, or as GENOME=FILE
+    fn annotation_genome(annotation: &str) -> (String, &str) {
+        if !path::Path::new(annotation).exists() {
+            if let Some((genome, file)) = annotation.split_once('=') {
+                return (genome.to_string(), file);
+            }
+        }
+        (genome_name_from_file(annotation), annotation)
+    }
+
+    // Gene name -> orthogroup, for each genome (column) of the table
+    fn parse_orthogroup_table(
+        filename: &str,
+        strict: bool,
+    ) -> Result<IndexMap<String, HashMap<String, String>>> {
+        let reader = bufreader_from_compressed_file(filename)?;
+        let mut report = ParseReport::new(filename, strict);
+
+        let mut lines = reader.lines();
+        let header = match lines.next() {
+            Some(line) => line.map_err(|e| report.read_error(e))?,
+            None => bail!("{filename} is empty"),
+        };
+        let mut genomes: IndexMap<String, HashMap<String, String>> = IndexMap::default();
+        for column in header.trim_end_matches('\r').split('\t').skip(1) {
+            genomes.insert(column.to_string(), HashMap::default());
+        }
+
+        for (i, line) in lines.enumerate() {
+            let line = line.map_err(|e| report.read_error(e))?;
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            let mut f = line.split('\t');
+            let orthogroup = f.next().unwrap_or_default();
+            let cells: Vec<&str> = f.collect();
+            if cells.len() > genomes.len() {
+                report.malformed(i + 2, format!("{} columns, the header has {}", cells.len() + 1, genomes.len() + 1))?;
+                continue;
+            }
+            for (genes, cell) in genomes.values_mut().zip(cells) {
+                for gene in cell.split(',').map(|g| g.trim()).filter(|g| !g.is_empty()) {
+                    genes.insert(gene.to_string(), orthogroup.to_string());
+                }
+            }
+        }
+        report.summarize();
+
+        Ok(genomes)
+    }
+
+    fn parse_feature(line: &[u8]) -> Result<(Feature, Vec<String>), String> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let line = str::from_utf8(line).map_err(|_| "line is not valid UTF-8".to_string())?;
+        let f: Vec<&str> = line.splitn(9, '\t').collect();
+        if f.len() < 9 {
+            return Err(format!("{} columns instead of 9", f.len()));
+        }
+        let position = |field: &str, name: &str| {
+            field
+                .parse::<usize>()
+                .map_err(|_| format!("invalid {name} position '{field}'"))
+        };
+
+        let mut names = Vec::new();
+        let mut id = None;
+        let mut parent = None;
+        for key_val in f[8].split(';') {
+            if let Some((key, val)) = key_val.trim().split_once('=') {
+                if key == "ID" {
+                    id = Some(val.to_string());
+                } else if key == "Parent" {
+                    parent = val.split(',').next().map(|p| p.to_string());
+                }
+                if GENE_ATTRIBUTES.contains(&key) {
+                    names.extend(val.split(',').map(|v| v.to_string()));
+                }
+            }
+        }
+
+        Ok((
+            Feature {
+                seqid: f[0].to_string(),
+                start: position(f[3], "start")?,
+                end: position(f[4], "end")?,
+                plus: f[6] != "-",
+                id,
+                parent,
+            },
+            names,
+        ))
+    }
+
+    // Genes of an annotation with their orthogroup, sorted by position along each sequence.
+    // A gene gets the orthogroup of the first of its names, or of its children names, found in the table.
+    fn parse_annotation(
+        filename: &str,
+        orthogroup_of: &HashMap<String, String>,
+        strict: bool,
+        node_indexer: &mut NodeIndexer,
+    ) -> Result<PathBundle> {
+        let mut reader = bufreader_from_compressed_file(filename)?;
+        let mut report = ParseReport::new(filename, strict);
+
+        let mut features: Vec<Feature> = Vec::new();
+        let mut feature_ids: HashMap<String, usize> = HashMap::default();
+        let mut assigned: Vec<(usize, &str)> = Vec::new();
+        let mut header: HashMap<String, usize> = HashMap::default();
+
+        let mut buf = vec![];
+        let mut line_num = 0usize;
+        while reader.read_until(b'\n', &mut buf).map_err(|e| report.read_error(e))? > 0 {
+            line_num += 1;
+            if buf.starts_with(b"##FASTA") {
+                break;
+            } else if buf.starts_with(b"##sequence-region") {
+                let line = String::from_utf8_lossy(&buf);
+                let f: Vec<&str> = line.split_ascii_whitespace().collect();
+                match f.get(3).and_then(|end| end.parse::<usize>().ok()) {
+                    Some(end) => {
+                        header.insert(f[1].to_string(), end);
+                    }
+                    None => report.malformed(line_num, "invalid ##sequence-region header")?,
+                }
+            } else if !buf.trim_ascii().is_empty() && buf[0] != b'#' {
+                match Self::parse_feature(&buf) {
+                    Ok((feature, names)) => {
+                        let idx = features.len();
+                        if let Some(og) = names.iter().find_map(|name| orthogroup_of.get(name)) {
+                            assigned.push((idx, og));
+                        }
+                        if let Some(id) = &feature.id {
+                            feature_ids.entry(id.clone()).or_insert(idx);
+                        }
+                        features.push(feature);
+                    }
+                    Err(reason) => report.malformed(line_num, reason)?,
+                }
+            }
+            buf.clear();
+        }
+        report.summarize();
+
+        // Gene (feature without parent) of each feature assigned to an orthogroup
+        let mut genes: Vec<(usize, &str)> = Vec::new();
+        let mut seen: HashSet<usize> = HashSet::default();
+        for (mut idx, og) in assigned {
+            let mut depth = 0;
+            while let Some(&parent) = features[idx].parent.as_ref().and_then(|p| feature_ids.get(p)) {
+                if parent == idx || depth > features.len() {
+                    break;
+                }
+                idx = parent;
+                depth += 1;
+            }
+            if seen.insert(idx) {
+                genes.push((idx, og));
+            }
+        }
+        genes.sort_by_key(|&(idx, _)| (features[idx].start, features[idx].end));
+
+        let mut paths: IndexMap<&str, (Path, Vec<usize>, Vec<usize>)> = IndexMap::default();
+        for feature in features.iter() {
+            paths.entry(&feature.seqid).or_default();
+        }
+        for (idx, og) in genes {
+            let feature = &features[idx];
+            let (path, starts, ends) = paths.get_mut(feature.seqid.as_str()).unwrap();
+            path.push(SignedId {
+                id: node_indexer.id_for(og.as_bytes()),
+                plus: feature.plus,
+            });
+            starts.push(feature.start);
+            ends.push(feature.end);
+        }
+
+        let mut genome = PathBundle::default();
+        for (seqid, (path, starts, ends)) in paths {
+            if path.is_empty() {
+                continue;
+            }
+            let size = header.get(seqid).copied().or_else(|| ends.iter().max().copied());
+            genome.paths.push(path);
+            genome.path_names.push(seqid.to_string());
+            genome.path_starts.push(starts);
+            genome.path_ends.push(ends);
+            genome.path_sizes.push(size.unwrap_or(0));
+        }
+        Ok(genome)
+    }
+
+    pub fn parse_orthogroup_paths(
+        filename: &str,
+        annotations: &[String],
+        group_by: bool,
+        strict: bool,
+    ) -> Result<(GenomeBundle, usize)> {
+        if annotations.is_empty() {
+            bail!("an orthogroup table needs the GFF3 annotation of each genome (--annotation)");
+        }
+        let table = Self::parse_orthogroup_table(filename, strict)?;
+
+        let mut genomes: IndexMap<String, PathBundle> = IndexMap::default();
+        let mut node_indexer = NodeIndexer::new();
+        let mut num_paths = 0usize;
+        let mut seen_genomes: HashSet<String> = HashSet::default();
+        for annotation in annotations.iter() {
+            let (genome_name, file) = Self::annotation_genome(annotation);
+            if !seen_genomes.insert(genome_name.clone()) {
+                bail!("genome {} is given twice", genome_name);
+            }
+            let Some(orthogroup_of) = table.get(&genome_name) else {
+                bail!(
+                    "genome {} of {} is not a column of {} (columns: {})",
+                    genome_name,
+                    file,
+                    filename,
+                    table.keys().cloned().collect::<Vec<_>>().join(", ")
+                );
+            };
+            let genome = Self::parse_annotation(file, orthogroup_of, strict, &mut node_indexer)?;
+            num_paths += genome.paths.len();
+
+            if group_by {
+                genomes.insert(genome_name, genome);
+            } else {
+                genomes.extend(genome.split_by_path(&genome_name));
+            }
+        }
+
+        let num_nodes = node_indexer.next;
+        let genome_bundle = GenomeBundle {
+            genomes,
+            num_paths,
+            node_indexer: Some(node_indexer),
+        };
+        Ok((genome_bundle, num_nodes))
+    }
+}
@@ -1,4 +1,7 @@
 pub mod cli;
 pub mod compression;
 pub mod io;
+pub mod synteny;
 pub mod collections;
+pub mod config;
+pub mod error;
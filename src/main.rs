@@ -1,14 +1,11 @@
 use anyhow::Result;
 use clap::Parser;
-
-mod cli;
-mod compression;
-mod collections;
-mod io;
+use mice::{cli, compression, config};
 
 fn main() -> Result<()> {
     let args = cli::Cli::parse();
-    compression::run_mice(&args)?;
+    let config = config::MiceConfig::try_from(&args)?;
+    compression::run_mice(&config)?;
 
     Ok(())
 }
@@ -0,0 +1,612 @@
+use crate::collections::{HashMap, HashSet, IndexMap};
+use anyhow::Result;
+use crate::io::{NodeIndexer, PathBundle, SignedId, FILTERED, UNINITIALIZED};
+
+/// What an interval of the block annotation covers
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IntervalKind {
+    /// An occurrence of the synteny block with this id
+    Block(usize),
+    /// Filtered elements inside a block
+    Filtered,
+    /// Gap between two consecutive elements inside a block
+    Ns,
+    /// Short insertion contracted inside a block (see `MiceConfig::max_gap`)
+    Insertion,
+}
+
+/// Share of the genomes containing a block: in (almost) every genome, in some of them,
+/// or in only a few of them
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BlockClass {
+    /// In at least 99% of the genomes, i.e. every genome under 100 genomes
+    Core,
+    Shell,
+    /// In a single genome, or in at most 15% of the genomes
+    Cloud,
+}
+
+impl BlockClass {
+    pub fn new(num_genomes: usize, total_genomes: usize) -> Self {
+        if num_genomes * 100 >= total_genomes * 99 {
+            BlockClass::Core
+        } else if num_genomes <= 1 || num_genomes * 100 <= total_genomes * 15 {
+            BlockClass::Cloud
+        } else {
+            BlockClass::Shell
+        }
+    }
+}
+
+impl std::fmt::Display for BlockClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BlockClass::Core => write!(f, "core"),
+            BlockClass::Shell => write!(f, "shell"),
+            BlockClass::Cloud => write!(f, "cloud"),
+        }
+    }
+}
+
+/// An interval of a path (1-based, inclusive)
+#[derive(Clone, Debug)]
+pub struct BlockInterval {
+    pub kind: IntervalKind,
+    pub start: usize,
+    pub end: usize,
+    pub plus: bool,
+}
+
+/// A synteny block, named after its core element, with all the elements it contains.
+/// `part_id` is the id given by the compression (core element index + 1),
+/// `id` is the id used in the outputs, different from `part_id` only if the blocks are renumbered.
+/// `num_genomes` is the number of genomes containing the block, `class` follows from it.
+/// `paralogs` names the split elements (see `MiceConfig::split_paralogs`) with copies in this block and in others.
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub id: usize,
+    pub part_id: usize,
+    pub core: String,
+    pub elements: Vec<String>,
+    pub num_genomes: usize,
+    pub class: BlockClass,
+    pub paralogs: Vec<String>,
+}
+
+/// A path rewritten as synteny blocks.
+/// `blocks` holds block ids (not element ids) with the orientation of each occurrence.
+/// `intervals` is None when the input has no element coordinates.
+/// `circular` is set for a circular chromosome, whose intervals start at the first block after the
+/// origin: a block crossing the origin ends past the path size (GFF3 convention).
+#[derive(Clone, Debug)]
+pub struct PathBlocks {
+    pub name: String,
+    pub size: Option<usize>,
+    pub circular: bool,
+    pub blocks: Vec<SignedId>,
+    pub intervals: Option<Vec<BlockInterval>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GenomeBlocks {
+    pub name: String,
+    pub paths: Vec<PathBlocks>,
+}
+
+/// Result of a mice run
+#[derive(Clone, Debug)]
+pub struct SyntenyResult {
+    pub blocks: Vec<Block>,
+    pub genomes: Vec<GenomeBlocks>,
+    pub num_nodes: usize,
+    pub num_paths: usize,
+}
+
+/// The blocks at one size threshold of a block hierarchy (see `MiceConfig::resolutions`).
+/// `parents[i]` is the id of the block containing `result.blocks[i]` at the next threshold,
+/// None if the block is filtered there or if this is the coarsest level.
+#[derive(Clone, Debug)]
+pub struct SyntenyLevel {
+    pub threshold: usize,
+    pub result: SyntenyResult,
+    pub parents: Vec<Option<usize>>,
+}
+
+impl SyntenyResult {
+    /// `genome_counts` is the number of genomes containing each element. The blocks of the
+    /// `accessory` elements (see `MiceConfig::compress_accessory`) are nested in the intervals of
+    /// the other blocks, which span them.
+    pub fn new(
+        genomes: &IndexMap<String, PathBundle>,
+        num_nodes: usize,
+        num_paths: usize,
+        node_to_part: &[usize],
+        node_indexer: Option<NodeIndexer>,
+        genome_counts: &[usize],
+        accessory: &HashSet<usize>,
+    ) -> Self {
+        let blocks = Self::blocks(genomes.len(), num_nodes, node_to_part, node_indexer, genome_counts);
+
+        // Elements of one kind, the others being filtered
+        let layer = |of_accessory: bool| -> Vec<usize> {
+            node_to_part
+                .iter()
+                .enumerate()
+                .map(|(el, &part)| if accessory.contains(&el) == of_accessory { part } else { FILTERED })
+                .collect()
+        };
+        let layers = if accessory.is_empty() {
+            Vec::new()
+        } else {
+            vec![layer(false), layer(true)]
+        };
+
+        let genomes = genomes
+            .iter()
+            .map(|(genome_name, genome)| GenomeBlocks {
+                name: genome_name.clone(),
+                paths: genome
+                    .paths
+                    .iter()
+                    .enumerate()
+                    .map(|(i, path)| PathBlocks {
+                        name: genome.path_names[i].clone(),
+                        size: genome.path_sizes.get(i).copied(),
+                        circular: genome.is_circular(i),
+                        blocks: Self::path_blocks(path, node_to_part),
+                        intervals: match layers.as_slice() {
+                            [core, nested] => Self::intervals(genome_name, genome, i, core).zip(
+                                Self::intervals(genome_name, genome, i, nested),
+                            )
+                            .map(|(core, nested)| Self::nest_intervals(core, nested)),
+                            _ => Self::intervals(genome_name, genome, i, node_to_part),
+                        }
+                        .map(|intervals| Self::with_insertions(intervals, genome.path_insertions.get(i))),
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            blocks,
+            genomes,
+            num_nodes,
+            num_paths,
+        }
+    }
+
+    // Intervals of the path i of a genome, None without element coordinates
+    fn intervals(
+        genome_name: &str,
+        genome: &PathBundle,
+        i: usize,
+        node_to_part: &[usize],
+    ) -> Option<Vec<BlockInterval>> {
+        let path = &genome.paths[i];
+        let intervals = match (genome.path_starts.get(i), genome.path_ends.get(i)) {
+            (Some(path_starts), Some(path_ends)) if genome.is_circular(i) => {
+                let size = genome
+                    .path_sizes
+                    .get(i)
+                    .copied()
+                    .or_else(|| path_ends.iter().max().copied())
+                    .unwrap_or(0);
+                Some(Self::circular_path_intervals(
+                    genome_name,
+                    path,
+                    path_starts,
+                    path_ends,
+                    size,
+                    node_to_part,
+                ))
+            }
+            (Some(path_starts), Some(path_ends)) => Some(Self::path_intervals(
+                genome_name,
+                path,
+                path_starts,
+                path_ends,
+                node_to_part,
+            )),
+            _ => None,
+        };
+        // Zero-length elements (e.g. LN:i:0 segments) end before they start and cover nothing
+        intervals.map(|mut intervals| {
+            intervals.retain(|interval| interval.start <= interval.end);
+            intervals
+        })
+    }
+
+    // The accessory blocks replace the filtered intervals they overlap inside the other blocks
+    fn nest_intervals(mut core: Vec<BlockInterval>, nested: Vec<BlockInterval>) -> Vec<BlockInterval> {
+        core.retain(|interval| {
+            interval.kind != IntervalKind::Filtered
+                || !nested.iter().any(|n| {
+                    matches!(n.kind, IntervalKind::Block(_)) && n.start <= interval.end && interval.start <= n.end
+                })
+        });
+        core.extend(nested);
+        core.sort_by_key(|interval| interval.start);
+        core
+    }
+
+    pub fn has_coordinates(&self) -> bool {
+        self.genomes
+            .iter()
+            .all(|g| g.paths.iter().all(|p| p.intervals.is_some()))
+    }
+
+    /// Marks the blocks holding copies of the same split element, given the name and ids of its copies
+    pub fn set_paralogs(&mut self, paralogs: &[(String, Vec<usize>)], node_to_part: &[usize]) {
+        let block_idx: HashMap<usize, usize> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| (block.part_id, i))
+            .collect();
+        for (name, copies) in paralogs.iter() {
+            let mut blocks: Vec<usize> = copies
+                .iter()
+                .filter(|&&id| node_to_part[id] != FILTERED)
+                .filter_map(|&id| block_idx.get(&(node_to_part[id] + 1)).copied())
+                .collect();
+            blocks.sort_unstable();
+            blocks.dedup();
+            if blocks.len() > 1 {
+                for i in blocks {
+                    self.blocks[i].paralogs.push(name.clone());
+                }
+            }
+        }
+    }
+
+    /// Renumbers the blocks from 1 by first occurrence along the paths of the reference genome
+    /// (the first genome if None), then the remaining blocks by decreasing number of elements
+    /// and first occurrence in the other genomes.
+    pub fn renumber(&mut self, reference: Option<&str>) -> Result<()> {
+        let reference_idx = match reference {
+            Some(name) => self
+                .genomes
+                .iter()
+                .position(|g| g.name == name)
+                .ok_or_else(|| anyhow::anyhow!("reference genome {} not found", name))?,
+            None => 0,
+        };
+
+        // Current id -> position in self.blocks
+        let block_idx: HashMap<usize, usize> = self
+            .blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| (block.id, i))
+            .collect();
+
+        let mut order: Vec<usize> = Vec::with_capacity(self.blocks.len());
+        let mut placed = vec![false; self.blocks.len()];
+        let genome_order = std::iter::once(reference_idx)
+            .chain((0..self.genomes.len()).filter(|&g| g != reference_idx));
+        let mut first_occurrences: Vec<usize> = Vec::new();
+        for (g, genome_idx) in genome_order.enumerate() {
+            let Some(genome) = self.genomes.get(genome_idx) else {
+                break;
+            };
+            for path in genome.paths.iter() {
+                for block in path.blocks.iter() {
+                    let i = block_idx[&block.id];
+                    if !placed[i] {
+                        placed[i] = true;
+                        if g == 0 {
+                            order.push(i);
+                        } else {
+                            first_occurrences.push(i);
+                        }
+                    }
+                }
+            }
+        }
+        // Blocks not in the reference, stable sort keeps the first occurrence order on ties
+        first_occurrences.sort_by_key(|&i| std::cmp::Reverse(self.blocks[i].elements.len()));
+        order.extend(first_occurrences);
+        order.extend((0..self.blocks.len()).filter(|&i| !placed[i]));
+
+        let mut new_ids: HashMap<usize, usize> = HashMap::default();
+        for (new_idx, &i) in order.iter().enumerate() {
+            new_ids.insert(self.blocks[i].id, new_idx + 1);
+        }
+
+        for block in self.blocks.iter_mut() {
+            block.id = new_ids[&block.id];
+        }
+        self.blocks.sort_by_key(|block| block.id);
+        for genome in self.genomes.iter_mut() {
+            for path in genome.paths.iter_mut() {
+                for block in path.blocks.iter_mut() {
+                    block.id = new_ids[&block.id];
+                }
+                for interval in path.intervals.iter_mut().flatten() {
+                    if let IntervalKind::Block(id) = interval.kind {
+                        interval.kind = IntervalKind::Block(new_ids[&id]);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn blocks(
+        num_genomes: usize,
+        num_nodes: usize,
+        node_to_part: &[usize],
+        node_indexer: Option<NodeIndexer>,
+        genome_counts: &[usize],
+    ) -> Vec<Block> {
+        let mut id_to_node_str = vec![Vec::new(); num_nodes];
+        if let Some(node_indexer) = node_indexer {
+            for (node_str, id) in node_indexer.map {
+                id_to_node_str[id] = node_str;
+            }
+        } else {
+            // default 1-based index
+            id_to_node_str = (1..=num_nodes).map(|x| x.to_string().into_bytes()).collect();
+        }
+        let node_name = |id: usize| String::from_utf8_lossy(&id_to_node_str[id]).into_owned();
+
+        let mut partition: Vec<Vec<usize>> = (0..num_nodes).map(|_| Vec::new()).collect();
+        for (id, &part) in node_to_part.iter().enumerate() {
+            if part != FILTERED && part < num_nodes {
+                partition[part].push(id);
+            }
+        }
+
+        // The core element of a block is in every occurrence of the block
+        partition
+            .iter()
+            .enumerate()
+            .filter(|(_, part)| !part.is_empty())
+            .map(|(core_id, part)| Block {
+                id: core_id + 1,
+                part_id: core_id + 1,
+                core: node_name(core_id),
+                elements: part.iter().map(|&id| node_name(id)).collect(),
+                num_genomes: genome_counts[core_id],
+                class: BlockClass::new(genome_counts[core_id], num_genomes),
+                paralogs: Vec::new(),
+            })
+            .collect()
+    }
+
+    // Only the core element of a partition is kept
+    fn path_blocks(path: &[SignedId], node_to_part: &[usize]) -> Vec<SignedId> {
+        path.iter()
+            .filter(|el| node_to_part[el.id] == el.id)
+            .map(|el| SignedId {
+                id: node_to_part[el.id] + 1,
+                plus: el.plus,
+            })
+            .collect()
+    }
+
+    // The gap left by a contracted insertion is the insertion, not Ns
+    fn with_insertions(mut intervals: Vec<BlockInterval>, insertions: Option<&Vec<(usize, usize)>>) -> Vec<BlockInterval> {
+        let Some(insertions) = insertions.filter(|insertions| !insertions.is_empty()) else {
+            return intervals;
+        };
+        intervals.retain(|interval| {
+            interval.kind != IntervalKind::Ns
+                || !insertions.iter().any(|&(start, end)| interval.start <= end && start <= interval.end)
+        });
+        for &(start, end) in insertions.iter() {
+            let plus = intervals
+                .iter()
+                .find(|interval| {
+                    matches!(interval.kind, IntervalKind::Block(_)) && interval.start <= start && end <= interval.end
+                })
+                .is_none_or(|interval| interval.plus);
+            intervals.push(BlockInterval {
+                kind: IntervalKind::Insertion,
+                start,
+                end,
+                plus,
+            });
+        }
+        // An insertion comes after the block containing it, stable sort keeps that order on ties
+        intervals.sort_by_key(|interval| interval.start);
+        intervals
+    }
+
+    // The path is read from its first element starting a block after the origin, the elements
+    // before it are moved after the end of the path, so a block crossing the origin is one interval.
+    // A path of a single block starts after its largest gap.
+    fn circular_path_intervals(
+        genome_name: &str,
+        path: &[SignedId],
+        path_starts: &[usize],
+        path_ends: &[usize],
+        size: usize,
+        node_to_part: &[usize],
+    ) -> Vec<BlockInterval> {
+        let part = |i: usize| node_to_part[path[i].id];
+        let kept: Vec<usize> = (0..path.len()).filter(|&i| part(i) != FILTERED).collect();
+        let previous = |k: usize| kept[(k + kept.len() - 1) % kept.len()];
+        let rotation = (0..kept.len())
+            .find(|&k| part(kept[k]) != part(previous(k)))
+            .or_else(|| {
+                let gap = |k: usize| {
+                    let wrap = if k == 0 { size } else { 0 };
+                    (path_starts[kept[k]] + wrap).saturating_sub(path_ends[previous(k)])
+                };
+                (0..kept.len()).max_by_key(|&k| (gap(k), std::cmp::Reverse(k)))
+            })
+            .map_or(0, |k| kept[k]);
+        if rotation == 0 {
+            return Self::path_intervals(genome_name, path, path_starts, path_ends, node_to_part);
+        }
+
+        let order: Vec<usize> = (rotation..path.len()).chain(0..rotation).collect();
+        let shift = |i: usize| if i < rotation { size } else { 0 };
+        let rotated: Vec<SignedId> = order.iter().map(|&i| path[i]).collect();
+        let starts: Vec<usize> = order.iter().map(|&i| path_starts[i] + shift(i)).collect();
+        let ends: Vec<usize> = order.iter().map(|&i| path_ends[i] + shift(i)).collect();
+
+        let mut intervals = Self::path_intervals(genome_name, &rotated, &starts, &ends, node_to_part);
+        for interval in intervals.iter_mut() {
+            if interval.start > size {
+                interval.start -= size;
+                interval.end -= size;
+            }
+        }
+        intervals
+    }
+
+    fn path_intervals(
+        genome_name: &str,
+        path: &[SignedId],
+        path_starts: &[usize],
+        path_ends: &[usize],
+        node_to_part: &[usize],
+    ) -> Vec<BlockInterval> {
+        let mut intervals = Vec::new();
+        let mut i = 0usize;
+        while i < path.len() {
+            while i < path.len() && node_to_part[path[i].id] == FILTERED {
+                i += 1;
+            }
+            if i == path.len() {
+                break;
+            }
+
+            let part_i = node_to_part[path[i].id]; //First non-FILTERED
+            let mut j = i;
+            while j + 1 < path.len()
+                && (part_i == node_to_part[path[j + 1].id]
+                    || node_to_part[path[j + 1].id] == FILTERED)
+            {
+                j += 1;
+            }
+
+            let end = j;
+            while node_to_part[path[j].id] == FILTERED {
+                j -= 1;
+            }
+            // This is true for the clean mode, but not for the dirty
+            // This is the situation:
+            //               ...i            j      ...
+            // node_to_part: ...pi pi F F pi pi F px...  (pi = part_i, px!=pi)
+
+            //There are two cases:
+            // node_to_part: ...pi pi F F pi pi...  (pi = part_i)
+            // core:         ...0  1  0 0 0  0 ...
+            // or
+            // core:         ...1  1  0 0 1  1 ...
+
+            //Count number of core elements (either 1 or many)
+            let mut count_core = 0usize;
+            let mut last_el_core = SignedId {
+                id: UNINITIALIZED,
+                plus: false,
+            };
+            for l in i..=j {
+                if node_to_part[path[l].id] == path[l].id {
+                    count_core += 1;
+                    last_el_core = path[l];
+                }
+            }
+
+            match count_core {
+                1 => {
+                    let part = node_to_part[last_el_core.id];
+                    let plus = last_el_core.plus;
+                    intervals.push(BlockInterval {
+                        kind: IntervalKind::Block(part + 1),
+                        start: path_starts[i],
+                        end: path_ends[j],
+                        plus,
+                    });
+
+                    // Obscured filtered ranges
+                    let mut l = i + 1;
+                    while l <= j {
+                        if node_to_part[path[l].id] == FILTERED {
+                            let start_filter_pos = l;
+                            let mut m = l + 1;
+                            while m <= j && node_to_part[path[m].id] == FILTERED {
+                                m += 1;
+                            }
+                            let end_filter_pos = m - 1;
+                            let left_start_filtered = path_starts[start_filter_pos];
+                            let right_end_filtered = path_ends[end_filter_pos];
+                            let left_end_part = path_ends[start_filter_pos - 1];
+                            let right_start_part = path_starts[end_filter_pos + 1];
+                            if right_start_part > left_end_part + 1 {
+                                intervals.push(BlockInterval {
+                                    kind: IntervalKind::Filtered,
+                                    start: usize::max(left_start_filtered, left_end_part + 1),
+                                    end: usize::min(right_end_filtered, right_start_part - 1),
+                                    plus,
+                                });
+                            }
+                            l = m;
+                        }
+                        l += 1;
+                    }
+
+                    // Obscured Ns ranges
+                    for l in i..j {
+                        let left_end = path_ends[l];
+                        let right_start = path_starts[l + 1];
+                        if right_start > left_end + 1 {
+                            //No overlap
+                            intervals.push(BlockInterval {
+                                kind: IntervalKind::Ns,
+                                start: left_end + 1,
+                                end: right_start - 1,
+                                plus,
+                            });
+                        }
+                    }
+                }
+                n if n > 1 => {
+                    let mut l = i;
+                    while l <= j {
+                        let start = path_starts[l];
+                        while l <= j && node_to_part[path[l].id] != path[l].id {
+                            l += 1;
+                        }
+                        if l <= j {
+                            let part = node_to_part[path[l].id];
+                            let plus = path[l].plus;
+                            while l < j && node_to_part[path[l + 1].id] != path[l + 1].id {
+                                l += 1;
+                            }
+                            intervals.push(BlockInterval {
+                                kind: IntervalKind::Block(part + 1),
+                                start,
+                                end: path_ends[l],
+                                plus,
+                            });
+                            l += 1;
+                        }
+                    }
+                }
+                _ => {
+                    eprintln!("Error: no core found in a substring in {genome_name}:");
+                    let part = node_to_part[path[i].id];
+                    for x in path[i..=j].iter() {
+                        if part != node_to_part[x.id] {
+                            eprintln!("the parts are not correct");
+                        }
+                    }
+                    eprintln!("part: {}", part + 1);
+                    for x in path[i..=j].iter() {
+                        eprint!("{}{} ", x.id + 1, if x.plus { '+' } else { '-' });
+                    }
+                    eprintln!();
+                    break;
+                }
+            }
+            i = end + 1;
+        }
+
+        intervals
+    }
+}
@@ -1,4 +1,11 @@
+use clap::Parser;
+use mice::cli::Cli;
 use mice::compression::*;
+use mice::config::{GenomeRule, MiceConfig, MinGenomes};
+use mice::error::MiceError;
+use mice::io::write_paralogs_to;
+use mice::synteny::{BlockClass, IntervalKind};
+use std::collections::HashSet;
 use std::error::Error;
 use std::fs;
 use std::io::{BufRead, BufReader};
@@ -20,6 +27,793 @@ fn compare_all_gfas_real() -> Result<(), Box<dyn Error>> {
     compare_gfa_dir(&gfa_dir, &out_dir)
 }
 
+#[test]
+fn synteny_result_from_gfa() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("synteny_result.gfa");
+
+    let config = MiceConfig::builder(&gfa).build()?;
+    let result = compute_synteny(&config)?;
+
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(result.blocks[0].elements.len(), 3);
+    assert!(result.has_coordinates());
+    for genome in result.genomes.iter() {
+        let path = &genome.paths[0];
+        assert_eq!(path.blocks.len(), 1);
+        assert_eq!(path.size, Some(9));
+        let intervals = path.intervals.as_ref().unwrap();
+        assert_eq!(intervals.len(), 1);
+        assert_eq!((intervals[0].start, intervals[0].end), (1, 9));
+    }
+    Ok(())
+}
+
+#[test]
+fn compressed_inputs() -> Result<(), Box<dyn Error>> {
+    use std::io::Write;
+
+    let plain = fs::read(fixture("synteny_result.gfa"))?;
+    let dir = TempDir::new("compressed_inputs")?;
+
+    let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(&plain)?;
+    let gz = gz.finish()?;
+
+    // two frames, to read past the end of the first one
+    let half = plain.len() / 2;
+    let mut zst = ruzstd::encoding::compress_to_vec(&plain[..half], ruzstd::encoding::CompressionLevel::Fastest);
+    zst.extend(ruzstd::encoding::compress_to_vec(&plain[half..], ruzstd::encoding::CompressionLevel::Fastest));
+
+    let mut bz2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    bz2.write_all(&plain)?;
+    let bz2 = bz2.finish()?;
+
+    let mut xz = lzma_rust2::XzWriter::new(Vec::new(), lzma_rust2::XzOptions::default())?;
+    xz.write_all(&plain)?;
+    let xz = xz.finish()?;
+
+    // the last one has no compression extension: only its first bytes tell it is compressed
+    for (name, data) in [("graph.gfa.gz", &gz), ("graph.gfa.zst", &zst), ("graph.gfa.bz2", &bz2),
+                         ("graph.gfa.xz", &xz), ("graph.gfa", &xz)] {
+        let path = dir.path().join(name);
+        fs::write(&path, data)?;
+        let config = MiceConfig::builder(path.to_str().unwrap()).build()?;
+        let result = compute_synteny(&config)?;
+        assert_eq!(result.blocks.len(), 1, "{name}");
+        assert_eq!(result.blocks[0].elements.len(), 3, "{name}");
+        assert_eq!(result.genomes.len(), 2, "{name}");
+    }
+    Ok(())
+}
+
+#[test]
+fn gfa_block_graph_output() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("two_blocks.gfa");
+    let dir = TempDir::new("gfa_block_graph_output")?;
+
+    let config = MiceConfig::builder(&gfa).renumber(true).reference("g1").out_dir(dir.path()).build()?;
+    run_mice(&config)?;
+
+    // segments in order of first appearance, one link between the blocks, one path per genome path
+    let output = fs::read_to_string(dir.path().join("output.gfa"))?;
+    let expected = [
+        "H\tVN:Z:1.0",
+        "S\t2\t*\tCL:Z:core",
+        "S\t1\t*\tCL:Z:core",
+        "L\t2\t+\t1\t+\t0M",
+        "P\tg2#1\t2+,1+\t*",
+        "P\tg1#1\t1+\t*",
+        "P\tg1#2\t2+\t*",
+    ];
+    assert_eq!(output.lines().collect::<Vec<_>>(), expected);
+    Ok(())
+}
+
+#[test]
+fn gfa_walks_as_paths() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("walks.gfa");
+
+    let config = MiceConfig::builder(&gfa).build()?;
+    let result = compute_synteny(&config)?;
+
+    // a walk is named sample#hap#seq and grouped by its sample
+    let names: Vec<&str> = result.genomes.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(names, vec!["s1", "s2"]);
+    assert_eq!(result.genomes[0].paths[0].name, "s1#1#chr1");
+    assert_eq!(result.genomes[1].paths[0].name, "s2#0#chr1");
+
+    // <c<b<a is the reverse of >a>b>c, and starts at the walk start
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(result.blocks[0].elements.len(), 3);
+    let s1 = &result.genomes[0].paths[0];
+    let s2 = &result.genomes[1].paths[0];
+    assert_ne!(s1.blocks[0].plus, s2.blocks[0].plus);
+    let interval = &s2.intervals.as_ref().unwrap()[0];
+    assert_eq!((interval.start, interval.end), (101, 109));
+    Ok(())
+}
+
+#[test]
+fn gff_from_gfa_segment_lengths() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("two_blocks.gfa");
+    let dir = TempDir::new("gff_from_gfa_segment_lengths")?;
+
+    let config = MiceConfig::builder(&gfa).renumber(true).out_dir(dir.path()).build()?;
+    run_mice(&config)?;
+
+    // c (3 bp) and d (5 bp) then a (4 bp) and b (2 bp) along g2#1
+    let output = fs::read_to_string(dir.path().join("output.gff"))?;
+    let blocks: Vec<(&str, &str, &str, &str)> = output
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .map(|l| {
+            let fields: Vec<&str> = l.split('\t').collect();
+            (fields[0], fields[3], fields[4], fields[8].split(';').next().unwrap())
+        })
+        .collect();
+    assert_eq!(
+        blocks,
+        vec![
+            ("g2#1", "1", "8", "ID=1"),
+            ("g2#1", "9", "14", "ID=2"),
+            ("g1#1", "1", "6", "ID=2"),
+            ("g1#2", "1", "8", "ID=1"),
+        ]
+    );
+    assert!(output.contains("##sequence-region g2#1 1 14\n"));
+    Ok(())
+}
+
+#[test]
+fn config_builder_validation() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("synteny_result.gfa");
+
+    let invalid = [
+        MiceConfig::builder("-").build(),
+        MiceConfig::builder(&gfa).remove_duplicates(1).build(),
+        MiceConfig::builder(&gfa).kmer_size(0).build(),
+        MiceConfig::builder(&gfa).kmer_size(33).build(),
+        MiceConfig::builder(&gfa).window(0).build(),
+        MiceConfig::builder(&gfa).resolutions([1000, 100]).build(),
+        MiceConfig::builder(&gfa).resolutions([0, 100]).min_size(10).build(),
+        MiceConfig::builder(&gfa).compress_accessory(true).build(),
+    ];
+    for (i, config) in invalid.iter().enumerate() {
+        assert!(config.is_err(), "invalid config {i} was built");
+    }
+
+    // stdin is accepted with a format
+    let config = MiceConfig::builder("-").format("gfa").build()?;
+    assert_eq!(config.format.as_deref(), Some("gfa"));
+    Ok(())
+}
+
+#[test]
+fn genome_order_kept_or_sorted() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("two_blocks.gfa");
+
+    // g2 comes first in the input
+    let result = compute_synteny(&MiceConfig::builder(&gfa).build()?)?;
+    let names: Vec<&str> = result.genomes.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(names, vec!["g2", "g1"]);
+    let paths: Vec<&str> = result.genomes[1].paths.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(paths, vec!["g1#1", "g1#2"]);
+
+    let result = compute_synteny(&MiceConfig::builder(&gfa).sort(true).build()?)?;
+    let names: Vec<&str> = result.genomes.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(names, vec!["g1", "g2"]);
+
+    // identical inputs give identical outputs
+    let dirs = [TempDir::new("genome_order_1")?, TempDir::new("genome_order_2")?];
+    for dir in dirs.iter() {
+        run_mice(&MiceConfig::builder(&gfa).out_dir(dir.path()).build()?)?;
+    }
+    for name in ["paths.txt", "partitions.txt", "output.gfa", "output.gff"] {
+        assert_eq!(fs::read(dirs[0].path().join(name))?, fs::read(dirs[1].path().join(name))?, "{name}");
+    }
+    Ok(())
+}
+
+#[test]
+fn blocks_renumbered_by_reference() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("two_blocks.gfa");
+    let dir = TempDir::new("blocks_renumbered_by_reference")?;
+
+    // the first genome (g2) is the reference by default: c,d comes first
+    let config = MiceConfig::builder(&gfa).renumber(true).build()?;
+    let result = compute_synteny(&config)?;
+    let ids: Vec<(usize, &str)> = result.blocks.iter().map(|b| (b.id, b.core.as_str())).collect();
+    assert_eq!(ids, vec![(1, "c"), (2, "a")]);
+
+    let config = MiceConfig::builder(&gfa).renumber(true).reference("g1").out_dir(dir.path()).build()?;
+    run_mice(&config)?;
+    let result = compute_synteny(&config)?;
+    let ids: Vec<(usize, &str)> = result.blocks.iter().map(|b| (b.id, b.core.as_str())).collect();
+    assert_eq!(ids, vec![(1, "a"), (2, "c")]);
+
+    // block_ids.txt maps the new ids to the ids without renumbering
+    let plain = compute_synteny(&MiceConfig::builder(&gfa).build()?)?;
+    let previous = |core: &str| plain.blocks.iter().find(|b| b.core == core).unwrap().id;
+    let block_ids = fs::read_to_string(dir.path().join("block_ids.txt"))?;
+    let expected = vec![
+        "#block_id\tprevious_id\tcore_element".to_string(),
+        format!("1\t{}\ta", previous("a")),
+        format!("2\t{}\tc", previous("c")),
+    ];
+    assert_eq!(block_ids.lines().collect::<Vec<_>>(), expected);
+
+    let config = MiceConfig::builder(&gfa).renumber(true).reference("g3").build()?;
+    assert!(compute_synteny(&config).is_err());
+    Ok(())
+}
+
+#[test]
+fn stdin_input_and_stdout_output() -> Result<(), Box<dyn Error>> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let gfa = fs::read(fixture("walks.gfa"))?;
+    let dir = TempDir::new("stdin_input_and_stdout_output")?;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mice"))
+        .args(["-", "--format", "gfa", "--stdout", "gff", "-o"])
+        .arg(dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(&gfa)?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+
+    // the streamed GFF is the one written to the output directory
+    let streamed = String::from_utf8(output.stdout)?;
+    assert!(streamed.starts_with("##gff-version 3\n"));
+    assert_eq!(streamed, fs::read_to_string(dir.path().join("output.gff"))?);
+
+    // stdin has no extension to infer the format from
+    let status = Command::new(env!("CARGO_BIN_EXE_mice"))
+        .args(["-", "-o"])
+        .arg(dir.path())
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    assert!(!status.success());
+    Ok(())
+}
+
+#[test]
+fn empty_segment_in_walks() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("empty_segment_walks.gfa");
+
+    // walks share the coordinates of P-lines: z (LN:i:0) covers nothing, s2 starts at 100
+    let result = compute_synteny(&MiceConfig::builder(&gfa).build()?)?;
+    assert_eq!(result.blocks.len(), 3);
+    for (genome, expected) in result.genomes.iter().zip([[(1, 10), (11, 20)], [(101, 110), (111, 120)]]) {
+        let intervals = genome.paths[0].intervals.as_ref().unwrap();
+        let coordinates: Vec<(usize, usize)> = intervals.iter().map(|i| (i.start, i.end)).collect();
+        assert_eq!(coordinates, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn empty_segment_with_min_size() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("empty_segment.gfa");
+
+    // z (LN:i:0) is left unmerged, then filtered as shorter than the minimum size
+    let config = MiceConfig::builder(&gfa).min_size(5).build()?;
+    let result = compute_synteny(&config)?;
+    assert_eq!(result.blocks.len(), 2);
+    assert!(result.blocks.iter().all(|b| !b.elements.contains(&"z".to_string())));
+
+    // without the minimum size, z covers nothing in output.gff
+    let dir = TempDir::new("empty_segment_with_min_size")?;
+    run_mice(&MiceConfig::builder(&gfa).out_dir(dir.path()).build()?)?;
+    let output = fs::read_to_string(dir.path().join("output.gff"))?;
+    let features: Vec<(usize, usize)> = output
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .map(|l| {
+            let fields: Vec<&str> = l.split('\t').collect();
+            (fields[3].parse().unwrap(), fields[4].parse().unwrap())
+        })
+        .collect();
+    assert_eq!(features.len(), 7);
+    assert!(features.iter().all(|(start, end)| start <= end));
+    Ok(())
+}
+
+#[test]
+fn strict_mode_reports_malformed_line() -> Result<(), Box<dyn Error>> {
+    let gff = fixture("malformed.gff");
+
+    let lenient = MiceConfig::builder(&gff).build()?;
+    assert_eq!(compute_synteny(&lenient)?.num_paths, 2);
+
+    let strict = MiceConfig::builder(&gff).strict(true).build()?;
+    let err = compute_synteny(&strict).unwrap_err();
+    match err.downcast_ref::<MiceError>() {
+        Some(MiceError::Malformed { line, .. }) => assert_eq!(*line, 3),
+        other => panic!("unexpected error {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn malformed_walk_reported() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("malformed_walks.gfa");
+
+    // walks with an empty segment name (">>" or a trailing ">") are skipped
+    let lenient = compute_synteny(&MiceConfig::builder(&gfa).build()?)?;
+    let names: Vec<&str> = lenient.genomes.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(names, vec!["s1", "s4"]);
+
+    let strict = MiceConfig::builder(&gfa).strict(true).build()?;
+    let err = compute_synteny(&strict).unwrap_err();
+    match err.downcast_ref::<MiceError>() {
+        Some(MiceError::Malformed { line, .. }) => assert_eq!(*line, 6),
+        other => panic!("unexpected error {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn gfa2_ordered_groups_as_paths() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("ordered_groups.gfa");
+
+    let config = MiceConfig::builder(&gfa).build()?;
+    let result = compute_synteny(&config)?;
+
+    assert_eq!(result.num_paths, 2);
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(result.genomes[0].paths[0].size, Some(5));
+    assert_eq!(result.genomes[1].paths[0].size, Some(5));
+
+    let gfa1 = fixture("ordered_groups_gfa1.gfa");
+    let config = MiceConfig::builder(&gfa1).build()?;
+    let err = compute_synteny(&config).unwrap_err();
+    assert!(matches!(err.downcast_ref::<MiceError>(), Some(MiceError::NoPaths { .. })));
+    Ok(())
+}
+
+#[test]
+fn orthogroups_with_annotations() -> Result<(), Box<dyn Error>> {
+    let table = fixture("orthogroups/Orthogroups.tsv");
+    let a = fixture("orthogroups/A.gff3");
+    let b = fixture("orthogroups/B.gff3");
+
+    // One annotation per option, the table stays the positional input
+    let args = Cli::try_parse_from(["mice", "-a", &a, "--annotation", &b, &table])?;
+    assert_eq!(args.graph_input, std::slice::from_ref(&table));
+    let config = MiceConfig::try_from(&args)?;
+    let result = compute_synteny(&config)?;
+
+    assert_eq!(result.num_paths, 2);
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(result.blocks[0].elements, vec!["OG1", "OG2"]);
+    let intervals = result.genomes[1].paths[0].intervals.as_ref().unwrap();
+    assert_eq!((intervals[0].start, intervals[0].end, intervals[0].plus), (5, 35, false));
+    Ok(())
+}
+
+#[test]
+fn gene_orders_round_trip() -> Result<(), Box<dyn Error>> {
+    let unimog = fixture("genomes.unimog");
+
+    let out_dir = TempDir::new("gene_orders")?;
+    let config = MiceConfig::builder(&unimog).out_dir(out_dir.path()).build()?;
+    run_mice(&config)?;
+    let output = fs::read_to_string(out_dir.path().join("output.unimog"))?;
+    // The circular chromosomes are the same circle read from another element
+    assert_eq!(output, ">A\n1 |\n4 )\n>B\n-1 |\n4 )\n");
+
+    let config = MiceConfig::builder(out_dir.path().join("output.unimog").to_string_lossy()).build()?;
+    let result = compute_synteny(&config)?;
+    assert_eq!(result.blocks.len(), 2);
+    assert!(!result.genomes[0].paths[0].circular);
+    assert!(result.genomes[0].paths[1].circular);
+    Ok(())
+}
+
+#[test]
+fn paths_file_as_input() -> Result<(), Box<dyn Error>> {
+    let paths = fixture("paths.txt");
+
+    let config = MiceConfig::builder(&paths).build()?;
+    let result = compute_synteny(&config)?;
+
+    assert_eq!(result.num_paths, 3);
+    assert_eq!(result.genomes.len(), 2);
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(result.blocks[0].elements, vec!["7", "3", "5"]);
+
+    // the block ids of separate runs cannot be merged
+    let config = MiceConfig::genome_files_builder().genome_file(&paths, None).genome_file(&paths, None).build()?;
+    assert!(compute_synteny(&config).is_err());
+    Ok(())
+}
+
+#[test]
+fn fasta_list_extensions() -> Result<(), Box<dyn Error>> {
+    // a list is one genome per FASTA file, named after the file
+    let config = MiceConfig::builder(fixture("fasta/genomes.lst")).kmer_size(15).window(5).build()?;
+    let result = compute_synteny(&config)?;
+    let names: Vec<&str> = result.genomes.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(names, vec!["A", "B"]);
+    assert_eq!(result.num_paths, 3);
+
+    // .txt is a paths file, a list under that name needs the format
+    let txt = fixture("fasta/genomes.txt");
+    assert!(compute_synteny(&MiceConfig::builder(&txt).strict(true).build()?).is_err());
+    let config = MiceConfig::builder(&txt).format("list").kmer_size(15).window(5).build()?;
+    assert_eq!(compute_synteny(&config)?.genomes.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn maf_blocks_as_elements() -> Result<(), Box<dyn Error>> {
+    let maf = fixture("alignment.maf");
+
+    let config = MiceConfig::builder(&maf).build()?;
+    let result = compute_synteny(&config)?;
+
+    assert_eq!(result.num_nodes, 2);
+    assert_eq!(result.blocks.len(), 1);
+    let b = &result.genomes[1].paths[0];
+    assert_eq!((b.name.as_str(), b.size), ("B.chr1", Some(50)));
+    let intervals = b.intervals.as_ref().unwrap();
+    assert_eq!((intervals[0].start, intervals[0].end, intervals[0].plus), (11, 25, false));
+
+    // the genome rule replaces the default '.' delimiter
+    let config = MiceConfig::builder(&maf).genome_rule(GenomeRule::Delimiter('#')).build()?;
+    let result = compute_synteny(&config)?;
+    let names: Vec<&str> = result.genomes.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(names, vec!["A.chr1", "B.chr1"]);
+    Ok(())
+}
+
+#[test]
+fn genome_per_input_file() -> Result<(), Box<dyn Error>> {
+    let a = fixture("genome_files/A.gff");
+    let b = fixture("genome_files/B.gfa");
+    let dir = TempDir::new("genome_per_input_file")?;
+
+    let config = MiceConfig::genome_files_builder()
+        .genome_file(&a, None)
+        .genome_file(&b, Some("Other".to_string()))
+        .out_dir(dir.path())
+        .build()?;
+    let result = compute_synteny(&config)?;
+
+    assert_eq!(result.num_paths, 3);
+    assert_eq!(result.genomes.len(), 2);
+    assert_eq!((result.genomes[0].name.as_str(), result.genomes[1].name.as_str()), ("A", "Other"));
+    assert_eq!(result.blocks.len(), 2);
+
+    // mixed formats are written as the block graph, with the block coordinates
+    run_mice(&config)?;
+    assert!(dir.path().join("output.gfa").exists());
+    assert!(dir.path().join("output.gff").exists());
+
+    let manifest = fixture("genome_files/genomes.manifest");
+    let config = MiceConfig::builder(&manifest).build()?;
+    assert_eq!(compute_synteny(&config)?.blocks.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn genome_from_seqid() -> Result<(), Box<dyn Error>> {
+    let gff = fixture("seqid_genomes.gff");
+
+    let config = MiceConfig::builder(&gff)
+        .genome_rule(GenomeRule::Delimiter('.'))
+        .build()?;
+    let result = compute_synteny(&config)?;
+    assert_eq!(result.genomes.len(), 2);
+    assert_eq!(result.genomes[1].name, "G2");
+    assert_eq!(result.genomes[1].paths.len(), 2);
+
+    let config = MiceConfig::builder(&gff)
+        .genome_rule(GenomeRule::regex(r"^G(\d)\.chr[12]$")?)
+        .build()?;
+    let result = compute_synteny(&config)?;
+    assert_eq!(result.num_paths, 3);
+    assert_eq!(result.genomes[0].name, "1");
+    Ok(())
+}
+
+#[test]
+fn genome_rules_on_gfa_paths() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("delimited.gfa");
+
+    // by default a P line is grouped by the prefix before '#', a W line by its sample
+    let result = compute_synteny(&MiceConfig::builder(&gfa).build()?)?;
+    assert_eq!(result.genomes.len(), 3);
+
+    // the delimiter applies to the W-line name sample#hap#seq too
+    let config = MiceConfig::builder(&gfa).genome_rule(GenomeRule::Delimiter('_')).build()?;
+    let result = compute_synteny(&config)?;
+    let names: Vec<&str> = result.genomes.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(names, vec!["G1", "G2"]);
+    assert_eq!(result.genomes[0].paths.len(), 2);
+    assert_eq!(result.blocks.len(), 2);
+
+    let config = MiceConfig::builder(&gfa)
+        .genome_rule(GenomeRule::mapping_file(&fixture("delimited.map"))?)
+        .build()?;
+    let result = compute_synteny(&config)?;
+    let names: Vec<&str> = result.genomes.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(names, vec!["A", "B"]);
+    assert_eq!(result.num_paths, 3);
+
+    // a GFA has no attribute to read the genome from
+    let config = MiceConfig::builder(&gfa).genome_rule(GenomeRule::Attribute("genome".to_string())).build()?;
+    let err = compute_synteny(&config).unwrap_err();
+    assert!(format!("{err:#}").contains("cannot be read from an attribute"));
+    Ok(())
+}
+
+#[test]
+fn gff_numeric_ids_kept() -> Result<(), Box<dyn Error>> {
+    let gff = fixture("sibeliaz.gff");
+    let dir = TempDir::new("gff_numeric_ids_kept")?;
+
+    let config = MiceConfig::builder(&gff).genome_rule(GenomeRule::Delimiter('.')).out_dir(dir.path()).build()?;
+    run_mice(&config)?;
+
+    // a block keeps the ID of its element, the name x is numbered after the largest ID
+    let output = fs::read_to_string(dir.path().join("output.gff"))?;
+    let ids: Vec<&str> = output
+        .lines()
+        .filter(|l| !l.starts_with('#'))
+        .map(|l| l.split('\t').nth(8).unwrap().split(';').next().unwrap())
+        .collect();
+    assert_eq!(ids, vec!["ID=5", "ID=9", "ID=10", "ID=9", "ID=5", "ID=10"]);
+    let paths = fs::read_to_string(dir.path().join("paths.txt"))?;
+    assert_eq!(paths.lines().nth(1), Some("5+,9+"));
+    Ok(())
+}
+
+#[test]
+fn gff_genome_from_seqid_without_attribute() -> Result<(), Box<dyn Error>> {
+    let gff = fixture("sibeliaz.gff");
+
+    // SibeliaZ writes no genome attribute, the genome is the seqid before the first '.'
+    let result = compute_synteny(&MiceConfig::builder(&gff).build()?)?;
+    let names: Vec<&str> = result.genomes.iter().map(|g| g.name.as_str()).collect();
+    assert_eq!(names, vec!["G1", "G2"]);
+    assert_eq!(result.genomes[0].paths.len(), 2);
+
+    // an explicit genome attribute has no fallback, and no row is left
+    let config = MiceConfig::builder(&gff).genome_rule(GenomeRule::Attribute("genome".to_string())).build()?;
+    let err = compute_synteny(&config).unwrap_err();
+    assert!(matches!(err.downcast_ref::<MiceError>(), Some(MiceError::NoPaths { .. })));
+    Ok(())
+}
+
+#[test]
+fn gff_elements_named_by_attribute() -> Result<(), Box<dyn Error>> {
+    let gff = fixture("clusters.gff");
+
+    let config = MiceConfig::builder(&gff).id_attribute("cluster").build()?;
+    let result = compute_synteny(&config)?;
+
+    assert_eq!(result.num_nodes, 2);
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(result.blocks[0].elements, vec!["OG_0042", "9000000000"]);
+    Ok(())
+}
+
+#[test]
+fn block_across_circular_origin() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("circular.gfa");
+
+    // g2 is linear: d and a are only adjacent around the origin of g1
+    let config = MiceConfig::builder(&gfa).build()?;
+    assert_eq!(compute_synteny(&config)?.blocks.len(), 4);
+
+    let config = MiceConfig::builder(&gfa).circular_paths(["g2#1"]).build()?;
+    let result = compute_synteny(&config)?;
+    assert_eq!(result.blocks.len(), 3);
+    let path = &result.genomes[0].paths[0];
+    assert!(path.circular);
+    let last = path.intervals.as_ref().unwrap().last().unwrap().clone();
+    assert_eq!((last.start, last.end), (31, 50));
+
+    // the block graph links the last block to the first one across the origin
+    let dir = TempDir::new("block_across_circular_origin")?;
+    run_mice(&MiceConfig::builder(&gfa).circular_paths(["g2#1"]).out_dir(dir.path()).build()?)?;
+    let output = fs::read_to_string(dir.path().join("output.gfa"))?;
+    let orient = |plus: bool| if plus { '+' } else { '-' };
+    let (first, last) = (path.blocks[0], *path.blocks.last().unwrap());
+    let link = format!("L\t{}\t{}\t{}\t{}\t0M", last.id, orient(last.plus), first.id, orient(first.plus));
+    let link_rev = format!("L\t{}\t{}\t{}\t{}\t0M", first.id, orient(!first.plus), last.id, orient(!last.plus));
+    assert!(output.lines().any(|line| line == link || line == link_rev), "{output}");
+    Ok(())
+}
+
+#[test]
+fn insertion_contracted_inside_block() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("insertion.gfa");
+
+    let config = MiceConfig::builder(&gfa).build()?;
+    assert_eq!(compute_synteny(&config)?.genomes[1].paths[0].blocks.len(), 4);
+
+    let config = MiceConfig::builder(&gfa).max_gap(1).build()?;
+    let result = compute_synteny(&config)?;
+    let path = &result.genomes[1].paths[0];
+    assert_eq!(path.blocks.len(), 2);
+    let intervals: Vec<(IntervalKind, usize, usize)> =
+        path.intervals.as_ref().unwrap().iter().map(|iv| (iv.kind, iv.start, iv.end)).collect();
+    assert_eq!(intervals[0].1..=intervals[0].2, 1..=25);
+    assert_eq!(intervals[1], (IntervalKind::Insertion, 11, 15));
+    assert_eq!(intervals[2].1..=intervals[2].2, 26..=35);
+
+    // u and w are only adjacent in another path of the same genome
+    let g4 = &result.genomes[3];
+    assert_eq!(g4.paths[0].blocks.len() + g4.paths[1].blocks.len(), 5);
+    Ok(())
+}
+
+#[test]
+fn insertion_contracted_everywhere_is_no_block() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("contracted.gfa");
+    let dir = TempDir::new("insertion_contracted_everywhere")?;
+
+    // t is only in g2, contracted inside the block a,b,c
+    let config = MiceConfig::builder(&gfa).max_gap(1).out_dir(dir.path()).build()?;
+    run_mice(&config)?;
+    let result = compute_synteny(&config)?;
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(result.blocks[0].class, BlockClass::Core);
+    assert!(result.blocks.iter().all(|b| !b.elements.contains(&"t".to_string())));
+
+    let partitions = fs::read_to_string(dir.path().join("partitions.txt"))?;
+    assert_eq!(partitions.lines().collect::<Vec<_>>(), vec!["a: a b c"]);
+    let gfa_out = fs::read_to_string(dir.path().join("output.gfa"))?;
+    assert_eq!(gfa_out.lines().filter(|l| l.starts_with("S\t")).count(), 1);
+    Ok(())
+}
+
+#[test]
+fn accessory_elements_by_genome_count() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("accessory.gfa");
+
+    let config = MiceConfig::builder(&gfa).build()?;
+    let result = compute_synteny(&config)?;
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(result.blocks[0].class, BlockClass::Core);
+
+    // x and y are filtered, the core elements form one block
+    let config = MiceConfig::builder(&gfa).min_genomes(MinGenomes::Count(3)).build()?;
+    let result = compute_synteny(&config)?;
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(result.blocks[0].elements, ["a", "b", "c"]);
+
+    // x and y are blocks of their own, nested in the block of the core elements
+    let config = MiceConfig::builder(&gfa)
+        .min_genomes("0.9".parse()?)
+        .compress_accessory(true)
+        .build()?;
+    let result = compute_synteny(&config)?;
+    let blocks: Vec<(&str, BlockClass, usize)> =
+        result.blocks.iter().map(|b| (b.core.as_str(), b.class, b.num_genomes)).collect();
+    assert_eq!(blocks, [("a", BlockClass::Core, 3), ("x", BlockClass::Shell, 2), ("y", BlockClass::Cloud, 1)]);
+    assert_eq!(result.blocks[0].elements, ["a", "b", "c"]);
+    let intervals: Vec<(IntervalKind, usize, usize)> =
+        result.genomes[2].paths[0].intervals.as_ref().unwrap().iter().map(|iv| (iv.kind, iv.start, iv.end)).collect();
+    assert_eq!(intervals, [(IntervalKind::Block(1), 1, 40), (IntervalKind::Block(5), 21, 30)]);
+    Ok(())
+}
+
+#[test]
+fn paralogs_split_by_context() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("paralogs.gfa");
+
+    // The copies of d are frozen, nothing is merged
+    let config = MiceConfig::builder(&gfa).build()?;
+    assert_eq!(compute_synteny(&config)?.blocks.len(), 5);
+
+    // Each copy of d joins the block of its neighbours
+    let config = MiceConfig::builder(&gfa).split_paralogs(true).build()?;
+    let result = compute_synteny(&config)?;
+    assert_eq!(result.blocks.len(), 2);
+    assert!(result.blocks.iter().all(|b| b.paralogs.contains(&"d".to_string())));
+    let mut out = Vec::new();
+    write_paralogs_to(&mut out, &result)?;
+    assert_eq!(String::from_utf8(out)?, "#element\tblock_ids\na\t3,5\nd\t3,5\n");
+
+    // Tandem copies differ by context, the single copy of g3 is not renamed
+    let tandem = fixture("tandem.gfa");
+    let config = MiceConfig::builder(&tandem).build()?;
+    assert_eq!(compute_synteny(&config)?.blocks.len(), 3);
+    let config = MiceConfig::builder(&tandem).split_paralogs(true).build()?;
+    let result = compute_synteny(&config)?;
+    assert_eq!(result.blocks.len(), 1);
+    assert_eq!(result.blocks[0].elements, ["x", "d", "y", "d.copy2", "d.copy3"]);
+    assert!(result.blocks[0].paralogs.is_empty());
+    Ok(())
+}
+
+#[test]
+fn block_hierarchy_by_size() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("hierarchy.gfa");
+
+    let config = MiceConfig::builder(&gfa).resolutions([0, 100]).build()?;
+    let levels = compute_hierarchy(&config)?;
+
+    assert_eq!(levels.len(), 2);
+    assert_eq!(levels[0].result.blocks.len(), 4);
+    assert_eq!(levels[1].result.blocks.len(), 1);
+    let coarse = levels[1].result.blocks[0].id;
+    let parents: Vec<Option<usize>> = levels[0].parents.clone();
+    assert_eq!(parents.iter().filter(|&&p| p == Some(coarse)).count(), 2);
+    assert_eq!(parents.iter().filter(|p| p.is_none()).count(), 2);
+    Ok(())
+}
+
+#[test]
+fn block_hierarchy_outputs() -> Result<(), Box<dyn Error>> {
+    let gfa = fixture("hierarchy.gfa");
+    let dir = TempDir::new("block_hierarchy_outputs")?;
+
+    let config = MiceConfig::builder(&gfa).resolutions([0, 100]).out_dir(dir.path()).build()?;
+    run_mice(&config)?;
+
+    // one line per block of the finest level, the short blocks have no parent
+    let hierarchy = fs::read_to_string(dir.path().join("hierarchy.txt"))?;
+    let lines: Vec<Vec<&str>> = hierarchy.lines().skip(1).map(|l| l.split('\t').collect()).collect();
+    assert_eq!(hierarchy.lines().next(), Some("#threshold\tblock_id\tparent_threshold\tparent_id"));
+    assert_eq!(lines.len(), 4);
+    assert!(lines.iter().all(|l| l[0] == "0" && l[2] == "100"));
+    assert_eq!(lines.iter().filter(|l| l[3] == ".").count(), 2);
+    let parents: HashSet<&str> = lines.iter().map(|l| l[3]).filter(|&p| p != ".").collect();
+    assert_eq!(parents.len(), 1);
+
+    // the coarser level is annotated apart, with a single block over a, b and c in g1
+    let gff = fs::read_to_string(dir.path().join("output.100.gff"))?;
+    let blocks: Vec<Vec<&str>> = gff
+        .lines()
+        .map(|l| l.split('\t').collect::<Vec<_>>())
+        .filter(|l| l.len() > 2 && l[2] == "SO:0000856")
+        .collect();
+    assert_eq!(blocks.len(), 2);
+    let parent = parents.into_iter().next().unwrap();
+    assert!(blocks.iter().all(|l| l[8].starts_with(&format!("ID={parent};"))));
+    assert!(blocks.iter().any(|l| l[0] == "g1#1" && (l[3], l[4]) == ("1", "1010")));
+    assert!(!dir.path().join("output.0.gff").exists());
+    Ok(())
+}
+
+// Input file checked in under tests/data/fixtures
+fn fixture(name: &str) -> String {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    root.join("tests/data/fixtures").join(name).to_string_lossy().into_owned()
+}
+
+// Output directory of a test, unique to the test process and removed at the end of the test
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> std::io::Result<Self> {
+        let dir = std::env::temp_dir().join(format!("mice_{name}_{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir)?;
+        Ok(Self(dir))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.0).ok();
+    }
+}
+
 fn compare_lines(stem: &str, got: &[String], expected: &[String]) -> Vec<String> {
     let mut failures: Vec<String> = Vec::new();
     let mut diff_count = 0usize;